@@ -0,0 +1,194 @@
+//! Drives the 2.9" V2 panel in 4-level grayscale mode.
+//!
+//! `epd-waveshare` only supports black and white on this panel, so the controller is initialized
+//! with the grayscale waveform from Waveshare's reference driver here. Each pixel is selected by
+//! one bit in the black/white RAM and one bit in the red RAM. The waveform maps the four
+//! combinations to black, dark gray, light gray and white.
+use anyhow::Result;
+use esp_idf_hal::{
+    gpio::{Input, Output, Pin, PinDriver},
+    spi::{SpiDeviceDriver, SpiDriver},
+};
+use log::info;
+use std::{thread, time::Duration};
+
+const WIDTH: u16 = 128;
+const HEIGHT: u16 = 296;
+
+// Grayscale waveform: the voltage sequences for each of the four RAM bit combinations, followed by
+// the phase timings, frame rate, gate and source voltages and VCOM.
+const LUT_GRAY: [u8; 159] = [
+    0x00, 0x60, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // VS L0
+    0x20, 0x60, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // VS L1
+    0x28, 0x60, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // VS L2
+    0x2A, 0x60, 0x15, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // VS L3
+    0x00, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // VS L4
+    0x00, 0x02, 0x00, 0x05, 0x14, 0x00, 0x00, // TP, SR, RP of group 0
+    0x1E, 0x1E, 0x00, 0x00, 0x00, 0x00, 0x01, // TP, SR, RP of group 1
+    0x00, 0x02, 0x00, 0x05, 0x14, 0x00, 0x00, // TP, SR, RP of group 2
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // TP, SR, RP of group 3
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // TP, SR, RP of group 4
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // TP, SR, RP of group 5
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // TP, SR, RP of group 6
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // TP, SR, RP of group 7
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // TP, SR, RP of group 8
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // TP, SR, RP of group 9
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // TP, SR, RP of group 10
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // TP, SR, RP of group 11
+    0x24, 0x22, 0x22, 0x22, 0x23, 0x32, 0x00, 0x00, 0x00, // FR, XON
+    0x22, 0x17, 0x41, 0xAE, 0x32, 0x28, // EOPT, VGH, VSH1, VSH2, VSL, VCOM
+];
+
+/// The 2.9" V2 panel in grayscale mode.
+pub struct Epd2in9Gray<'d, BUSY: Pin, DC: Pin, RST: Pin> {
+    busy: PinDriver<'d, BUSY, Input>,
+    dc: PinDriver<'d, DC, Output>,
+    rst: PinDriver<'d, RST, Output>,
+}
+
+impl<'d, BUSY: Pin, DC: Pin, RST: Pin> Epd2in9Gray<'d, BUSY, DC, RST> {
+    /// Resets the controller and loads the grayscale waveform.
+    pub fn new<'s>(
+        spi: &mut SpiDeviceDriver<'s, SpiDriver<'s>>,
+        busy: PinDriver<'d, BUSY, Input>,
+        dc: PinDriver<'d, DC, Output>,
+        rst: PinDriver<'d, RST, Output>,
+    ) -> Result<Self> {
+        let mut epd = Epd2in9Gray { busy, dc, rst };
+        epd.init(spi)?;
+        Ok(epd)
+    }
+
+    /// Draws an image with two bits per pixel, as served in the `epd_gray` format, and refreshes
+    /// the display.
+    pub fn display<'s>(
+        &mut self,
+        spi: &mut SpiDeviceDriver<'s, SpiDriver<'s>>,
+        image_data: &[u8],
+    ) -> Result<()> {
+        let (high, low) = split_gray_planes(image_data);
+
+        self.command(spi, 0x24, &low)?;
+        self.command(spi, 0x26, &high)?;
+
+        // Run the display update sequence with the waveform loaded at init.
+        self.command(spi, 0x22, &[0xC7])?;
+        self.command(spi, 0x20, &[])?;
+        self.wait_until_idle();
+
+        Ok(())
+    }
+
+    /// Returns the pins, e.g. to put the panel to sleep with the black and white driver.
+    pub fn release(
+        self,
+    ) -> (
+        PinDriver<'d, BUSY, Input>,
+        PinDriver<'d, DC, Output>,
+        PinDriver<'d, RST, Output>,
+    ) {
+        (self.busy, self.dc, self.rst)
+    }
+
+    fn init<'s>(&mut self, spi: &mut SpiDeviceDriver<'s, SpiDriver<'s>>) -> Result<()> {
+        self.rst.set_high()?;
+        thread::sleep(Duration::from_millis(10));
+        self.rst.set_low()?;
+        thread::sleep(Duration::from_millis(2));
+        self.rst.set_high()?;
+        thread::sleep(Duration::from_millis(10));
+        self.wait_until_idle();
+
+        // Software reset.
+        self.command(spi, 0x12, &[])?;
+        self.wait_until_idle();
+
+        // Driver output control: all gate lines, scanning from the first.
+        let [last_lo, last_hi] = (HEIGHT - 1).to_le_bytes();
+        self.command(spi, 0x01, &[last_lo, last_hi, 0x00])?;
+
+        // Data entry mode: increment x, then y, so that the RAM is filled row by row.
+        self.command(spi, 0x11, &[0x03])?;
+
+        // RAM window covering the whole panel, in bytes along x and lines along y.
+        self.command(spi, 0x44, &[0x00, ((WIDTH - 1) / 8) as u8])?;
+        self.command(spi, 0x45, &[0x00, 0x00, last_lo, last_hi])?;
+
+        // Border waveform.
+        self.command(spi, 0x3C, &[0x04])?;
+
+        // RAM address counters at the start of the window.
+        self.command(spi, 0x4E, &[0x00])?;
+        self.command(spi, 0x4F, &[0x00, 0x00])?;
+        self.wait_until_idle();
+
+        self.command(spi, 0x32, &LUT_GRAY[..153])?;
+        self.wait_until_idle();
+        self.command(spi, 0x3F, &LUT_GRAY[153..154])?;
+        // Gate voltage.
+        self.command(spi, 0x03, &LUT_GRAY[154..155])?;
+        // Source voltages VSH1, VSH2 and VSL.
+        self.command(spi, 0x04, &LUT_GRAY[155..158])?;
+        // VCOM.
+        self.command(spi, 0x2C, &LUT_GRAY[158..159])?;
+
+        info!("E-Ink display grayscale init completed!");
+        Ok(())
+    }
+
+    fn command<'s>(
+        &mut self,
+        spi: &mut SpiDeviceDriver<'s, SpiDriver<'s>>,
+        command: u8,
+        data: &[u8],
+    ) -> Result<()> {
+        self.dc.set_low()?;
+        spi.write(&[command])?;
+
+        if !data.is_empty() {
+            self.dc.set_high()?;
+            spi.write(data)?;
+        }
+
+        Ok(())
+    }
+
+    fn wait_until_idle(&self) {
+        while self.busy.is_high() {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+// Splits the 2-bit pixels of a grayscale image into two 1-bit planes, the high bits and the low
+// bits. White sets both bits, black none and the gray levels one each. The low bits go to the
+// black/white RAM and the high bits to the red RAM, which the waveform maps to the gray levels.
+fn split_gray_planes(image_data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut high = vec![0; image_data.len().div_ceil(2)];
+    let mut low = vec![0; image_data.len().div_ceil(2)];
+
+    for (i, byte) in image_data.iter().enumerate() {
+        for p in 0..4 {
+            let level = (byte >> (6 - 2 * p)) & 0b11;
+            let bit = 7 - (i % 2 * 4 + p);
+
+            high[i / 2] |= (level >> 1) << bit;
+            low[i / 2] |= (level & 1) << bit;
+        }
+    }
+
+    (high, low)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_planes() {
+        // Black, dark gray, light gray and white, then white, light gray, dark gray and black.
+        let (high, low) = split_gray_planes(&[0b00_01_10_11, 0b11_10_01_00, 0xff]);
+        assert_eq!(high, [0b0011_1100, 0b1111_0000]);
+        assert_eq!(low, [0b0101_1010, 0b1111_0000]);
+    }
+}
@@ -1,4 +1,3 @@
-use super::display_buffer_size;
 use anyhow::{Result, bail};
 use embedded_svc::{
     http::{Method, client::Client},
@@ -11,7 +10,7 @@ use esp_idf_svc::{
 use log::info;
 use std::time::Duration;
 
-pub fn fetch_image_data(url: &str) -> Result<Vec<u8>> {
+pub fn fetch_image_data(url: &str, buffer_size: usize) -> Result<Vec<u8>> {
     let connection = EspHttpConnection::new(&Configuration {
         timeout: Some(Duration::from_secs(5)),
         use_global_ca_store: true,
@@ -30,7 +29,7 @@ pub fn fetch_image_data(url: &str) -> Result<Vec<u8>> {
         bail!("Expected response code 200, got {status}");
     }
 
-    let mut buf = vec![0; display_buffer_size()];
+    let mut buf = vec![0; buffer_size];
     let len = io::try_read_full(response, &mut buf).map_err(|err| err.0)?;
 
     info!("Received {len} bytes");
@@ -1,3 +1,4 @@
+mod gray;
 mod http;
 mod wifi;
 
@@ -15,6 +16,7 @@ use esp_idf_hal::{
     spi::{SpiDeviceDriver, SpiDriverConfig, config::Config as SpiConfig},
 };
 use esp_idf_svc::{eventloop::EspSystemEventLoop, nvs::EspDefaultNvsPartition};
+use gray::Epd2in9Gray;
 use log::{error, info};
 use std::{thread, time::Duration};
 
@@ -30,6 +32,8 @@ pub struct Config {
     clear_after_seconds: u64,
     #[default("")]
     data_url: &'static str,
+    #[default(false)]
+    grayscale: bool,
 }
 
 fn main() -> Result<()> {
@@ -61,7 +65,13 @@ fn run(
     )
     .context("Could not connect to WiFi network")?;
 
-    let image_data = http::fetch_image_data(CONFIG.data_url)?;
+    let buffer_size = if CONFIG.grayscale {
+        gray_buffer_size()
+    } else {
+        display_buffer_size()
+    };
+
+    let image_data = http::fetch_image_data(CONFIG.data_url, buffer_size)?;
 
     info!("Disconnecting WiFi");
     drop(wifi);
@@ -87,11 +97,23 @@ fn run(
     let rst = PinDriver::output(peripherals.pins.gpio12)?;
     let mut delay = Ets;
 
-    let mut epd = Epd2in9::new(&mut spi, busy_in, dc, rst, &mut delay, None)?;
-    info!("E-Ink display init completed!");
+    let mut epd = if CONFIG.grayscale {
+        let mut epd = Epd2in9Gray::new(&mut spi, busy_in, dc, rst)?;
+
+        info!("Drawing grayscale image");
+        epd.display(&mut spi, &image_data)?;
+
+        // Clearing and sleeping work the same in both modes.
+        let (busy_in, dc, rst) = epd.release();
+        Epd2in9::new(&mut spi, busy_in, dc, rst, &mut delay, None)?
+    } else {
+        let mut epd = Epd2in9::new(&mut spi, busy_in, dc, rst, &mut delay, None)?;
+        info!("E-Ink display init completed!");
 
-    info!("Drawing image");
-    epd.update_and_display_frame(&mut spi, &image_data, &mut delay)?;
+        info!("Drawing image");
+        epd.update_and_display_frame(&mut spi, &image_data, &mut delay)?;
+        epd
+    };
 
     #[allow(clippy::absurd_extreme_comparisons)]
     if CONFIG.clear_after_seconds > 0 {
@@ -115,3 +137,8 @@ fn enter_deep_sleep(sleep_time: Duration) -> ! {
 const fn display_buffer_size() -> usize {
     buffer_len(WIDTH as usize, HEIGHT as usize)
 }
+
+// Grayscale images use two bits per pixel.
+const fn gray_buffer_size() -> usize {
+    2 * display_buffer_size()
+}
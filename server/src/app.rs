@@ -1,35 +1,80 @@
-use crate::{config::Config, error::Result, graphics::Renderer, weather::Weather};
+use crate::{
+    config::{Config, DEFAULT_LOCATION, Location},
+    error::Result,
+    graphics::Renderer,
+    weather::Weather,
+};
 use prometheus::{
     IntCounterVec, Registry,
     core::{AtomicU64, GenericCounter},
     opts,
 };
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::debug;
 
 /// Holds the application state.
 #[derive(Clone)]
 pub struct AppState {
-    pub metrics: Metrics,
-    pub renderer: Renderer,
-    pub weather: Weather,
+    locations: Arc<BTreeMap<String, LocationState>>,
 }
 
 impl AppState {
     /// Creates `AppState` from config and metrics.
     pub fn new(config: &Config, metrics: Metrics) -> Result<AppState> {
-        let weather = Weather::new(config.latitude, config.longitude, config.altitude)?;
-        let renderer = Renderer::new(config, metrics.clone());
+        let mut locations = BTreeMap::new();
+
+        for (name, location) in config.all_locations() {
+            let metrics = metrics.for_location(name);
+            let weather = Weather::from_config(config, name, &location)?;
+            let time_zone = location.time_zone()?;
+
+            debug!(location = name, time_zone = ?time_zone.iana_name(), "using time zone");
+
+            let renderer = Renderer::new(config, time_zone, metrics.clone());
+
+            locations.insert(
+                name.to_owned(),
+                LocationState {
+                    location,
+                    metrics,
+                    renderer,
+                    weather,
+                },
+            );
+        }
 
         Ok(AppState {
-            metrics,
-            renderer,
-            weather,
+            locations: Arc::new(locations),
         })
     }
+
+    /// Returns the state of the location called `name`, if it exists.
+    pub fn location(&self, name: &str) -> Option<&LocationState> {
+        self.locations.get(name)
+    }
+
+    /// Returns the state of all locations.
+    pub fn locations(&self) -> impl Iterator<Item = &LocationState> {
+        self.locations.values()
+    }
+}
+
+/// Holds the state of a single configured location.
+#[derive(Clone)]
+pub struct LocationState {
+    pub location: Location,
+    pub metrics: Metrics,
+    pub renderer: Renderer,
+    pub weather: Weather,
 }
 
 /// Container type for all custom application metrics.
+///
+/// All metrics are labeled with the location they were recorded for.
 #[derive(Clone, Debug)]
 pub struct Metrics {
+    location: Arc<str>,
     image_counter: IntCounterVec,
     object_counter: IntCounterVec,
 }
@@ -39,30 +84,73 @@ impl Metrics {
     pub fn new(namespace: &str, registry: &Registry) -> Result<Metrics> {
         let image_counter = IntCounterVec::new(
             opts!("image_requests_total", "Total number of image requests").namespace(namespace),
-            &["mime_type"],
+            &["location", "mime_type"],
         )?;
         let object_counter = IntCounterVec::new(
             opts!("rendered_objects_total", "Total number of rendered objects")
                 .namespace(namespace),
-            &["object"],
+            &["location", "object"],
         )?;
 
         registry.register(Box::new(image_counter.clone()))?;
         registry.register(Box::new(object_counter.clone()))?;
 
         Ok(Metrics {
+            location: Arc::from(DEFAULT_LOCATION),
             image_counter,
             object_counter,
         })
     }
 
+    /// Returns metrics which share the same collectors but are labeled with `location`.
+    pub fn for_location(&self, location: &str) -> Metrics {
+        Metrics {
+            location: Arc::from(location),
+            ..self.clone()
+        }
+    }
+
     /// Returns the image counter for given mime type.
     pub fn image_counter(&self, mime_type: &str) -> GenericCounter<AtomicU64> {
-        self.image_counter.with_label_values(&[mime_type])
+        self.image_counter
+            .with_label_values(&[&*self.location, mime_type])
     }
 
     /// Returns the counter for given object.
     pub fn object_counter(&self, object: &str) -> GenericCounter<AtomicU64> {
-        self.object_counter.with_label_values(&[object])
+        self.object_counter
+            .with_label_values(&[&*self.location, object])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn location_labels() {
+        let metrics = Metrics::new("test", &Registry::new()).unwrap();
+        let office = metrics.for_location("office");
+
+        office.image_counter("image/png").inc();
+        office.object_counter("house_00").inc();
+        metrics.image_counter("image/png").inc();
+
+        // The collectors are shared, only the labels differ.
+        let image_counter = |location| {
+            metrics
+                .image_counter
+                .with_label_values(&[location, "image/png"])
+                .get()
+        };
+        assert_eq!(image_counter("office"), 1);
+        assert_eq!(image_counter(DEFAULT_LOCATION), 1);
+        assert_eq!(
+            metrics
+                .object_counter
+                .with_label_values(&["office", "house_00"])
+                .get(),
+            1
+        );
     }
 }
@@ -1,7 +1,18 @@
-use crate::error::Result;
+use crate::{
+    error::{Error, Result},
+    graphics::{Display, Layout},
+};
 use config::{Environment, File};
+use jiff::{SignedDuration, Timestamp, tz::TimeZone};
 use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::iter;
+use std::path::PathBuf;
 use tracing::debug;
+use tzf_rs::DefaultFinder;
+
+/// Name of the location configured via the top-level coordinates.
+pub const DEFAULT_LOCATION: &str = "default";
 
 /// Application configuration sourced from env and/or config file.
 #[derive(Debug, Clone, Deserialize)]
@@ -9,8 +20,208 @@ pub struct Config {
     pub latitude: f64,
     pub longitude: f64,
     pub altitude: Option<i32>,
+    /// IANA name of the time zone at the location. Looked up from the coordinates if absent.
+    pub timezone: Option<String>,
+    /// Additional named locations.
+    #[serde(default)]
+    pub locations: BTreeMap<String, Location>,
     #[serde(default)]
     pub disable_night_mode: bool,
+    #[serde(default)]
+    pub units: Units,
+    #[serde(default)]
+    pub display: Display,
+    #[serde(default)]
+    pub layout: Layout,
+    /// Integer factor to draw sprites and the scene larger by. Derived from the display size if
+    /// absent.
+    pub scale: Option<u32>,
+    #[serde(default)]
+    pub provider: ProviderConfig,
+    #[serde(default = "default_max_data_age")]
+    pub max_data_age: SignedDuration,
+    pub state_dir: Option<PathBuf>,
+    #[serde(default = "default_forecast_hours")]
+    pub forecast_hours: usize,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub sprites: SpritesConfig,
+}
+
+/// A location to render weather images for.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<i32>,
+    /// IANA name of the time zone at the location. Looked up from the coordinates if absent.
+    pub timezone: Option<String>,
+}
+
+impl Location {
+    /// Returns the time zone of the location. If none is configured, it's looked up from the
+    /// coordinates using an embedded database of time zone boundaries.
+    pub fn time_zone(&self) -> Result<TimeZone> {
+        if let Some(name) = &self.timezone {
+            return Ok(TimeZone::get(name)?);
+        }
+
+        let finder = DefaultFinder::new();
+        let name = finder.get_tz_name(self.longitude, self.latitude);
+
+        if name.is_empty() {
+            return Err(Error::new(format!(
+                "no time zone found for coordinates {}, {}",
+                self.latitude, self.longitude
+            )));
+        }
+
+        Ok(TimeZone::get(name)?)
+    }
+}
+
+/// Selects the unit system for displayed values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    /// Degrees Celsius.
+    #[default]
+    Metric,
+    /// Degrees Fahrenheit.
+    Imperial,
+}
+
+impl Units {
+    /// Converts a temperature in degrees Celsius to the unit system.
+    pub fn temperature(self, celsius: f64) -> f64 {
+        match self {
+            Units::Metric => celsius,
+            Units::Imperial => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+}
+
+/// Selects the source of weather data.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProviderConfig {
+    /// Fetch weather data from api.met.no.
+    MetNo {
+        /// Directory to save every raw response to for later replay.
+        record_dir: Option<PathBuf>,
+        /// Base URL of the met.no API.
+        #[serde(default = "default_metno_base_url")]
+        base_url: String,
+    },
+    /// Fetch weather data from open-meteo.com.
+    OpenMeteo {
+        /// Base URL of the Open-Meteo API.
+        #[serde(default = "default_open_meteo_base_url")]
+        base_url: String,
+    },
+    /// Load met.no locationforecast documents from a file or directory. Useful for offline
+    /// development.
+    Fixture {
+        /// Path to a JSON document or a directory of JSON documents.
+        path: PathBuf,
+    },
+    /// Replay met.no responses saved via `record_dir`.
+    Replay {
+        /// Directory containing the recorded responses.
+        path: PathBuf,
+        /// Only replay the latest recording taken at or before this instant.
+        at: Option<Timestamp>,
+    },
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        ProviderConfig::MetNo {
+            record_dir: None,
+            base_url: default_metno_base_url(),
+        }
+    }
+}
+
+/// Configures official weather warnings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertsConfig {
+    /// Fetch warnings from the met.no MetAlerts API. Only locations in Norway are covered.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Base URL of the met.no API.
+    #[serde(default = "default_metno_base_url")]
+    pub base_url: String,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        AlertsConfig {
+            enabled: false,
+            base_url: default_metno_base_url(),
+        }
+    }
+}
+
+/// Configures the store of past observations.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryConfig {
+    /// Record the current weather of every refresh and show the past hours left of the house.
+    #[serde(default)]
+    pub enabled: bool,
+    /// The number of past hours shown in the image.
+    #[serde(default = "default_history_hours")]
+    pub hours: usize,
+    /// How long observations are kept before they are pruned.
+    #[serde(default = "default_history_retention")]
+    pub retention: SignedDuration,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            enabled: false,
+            hours: default_history_hours(),
+            retention: default_history_retention(),
+        }
+    }
+}
+
+/// Configures sprites which replace the embedded artwork.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SpritesConfig {
+    /// Directory with PNG files overriding the embedded sprites of the same name.
+    pub dir: Option<PathBuf>,
+    /// Reload the sprites whenever a file in `dir` changes. Meant for iterating on artwork.
+    #[serde(default)]
+    pub watch: bool,
+}
+
+fn default_forecast_hours() -> usize {
+    24
+}
+
+fn default_max_data_age() -> SignedDuration {
+    SignedDuration::from_hours(6)
+}
+
+fn default_history_hours() -> usize {
+    6
+}
+
+fn default_history_retention() -> SignedDuration {
+    SignedDuration::from_hours(48)
+}
+
+fn default_metno_base_url() -> String {
+    String::from("https://api.met.no")
+}
+
+fn default_open_meteo_base_url() -> String {
+    String::from("https://api.open-meteo.com")
 }
 
 impl Config {
@@ -22,10 +233,145 @@ impl Config {
             // Config from environment variables.
             .add_source(Environment::default().separator("_"))
             .build()?
-            .try_deserialize()?;
+            .try_deserialize::<Config>()?;
+
+        config.validate()?;
 
         debug!(?config, "configuration loaded");
 
         Ok(config)
     }
+
+    // Checks the values which cannot be expressed in the types.
+    fn validate(&self) -> Result<()> {
+        if !(6..=48).contains(&self.forecast_hours) {
+            return Err(Error::new(format!(
+                "forecast_hours must be between 6 and 48, got {}",
+                self.forecast_hours
+            )));
+        }
+
+        if let Some(scale) = self.scale.filter(|scale| !(1..=4).contains(scale)) {
+            return Err(Error::new(format!(
+                "scale must be between 1 and 4, got {scale}"
+            )));
+        }
+
+        if !(1..=24).contains(&self.history.hours) {
+            return Err(Error::new(format!(
+                "history.hours must be between 1 and 24, got {}",
+                self.history.hours
+            )));
+        }
+
+        if self.history.retention < SignedDuration::from_hours(self.history.hours as i64) {
+            return Err(Error::new(format!(
+                "history.retention must cover at least history.hours, got {:#}",
+                self.history.retention
+            )));
+        }
+
+        let mut names = HashSet::new();
+
+        for name in self.locations.keys() {
+            if name.eq_ignore_ascii_case(DEFAULT_LOCATION) {
+                return Err(Error::new(format!(
+                    "location name {DEFAULT_LOCATION:?} is reserved for the top-level coordinates"
+                )));
+            }
+
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(Error::new(format!(
+                    "invalid location name {name:?}: only ASCII letters, digits and '-' are allowed"
+                )));
+            }
+
+            // Names end up in URLs and directory names, which may be case-insensitive.
+            if !names.insert(name.to_ascii_lowercase()) {
+                return Err(Error::new(format!(
+                    "duplicate location name {name:?}: names must differ in more than case"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the default location and all named locations.
+    pub fn all_locations(&self) -> impl Iterator<Item = (&str, Location)> {
+        let default = Location {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            altitude: self.altitude,
+            timezone: self.timezone.clone(),
+        };
+
+        iter::once((DEFAULT_LOCATION, default)).chain(
+            self.locations
+                .iter()
+                .map(|(name, location)| (name.as_str(), location.clone())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(locations: serde_json::Value) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "latitude": 52.52,
+            "longitude": 13.405,
+            "locations": locations,
+        }))
+        .unwrap()
+    }
+
+    fn location() -> serde_json::Value {
+        serde_json::json!({ "latitude": 59.91, "longitude": 10.75 })
+    }
+
+    #[test]
+    fn valid_location_names() {
+        let config = config(serde_json::json!({
+            "office": location(),
+            "Cabin-2": location(),
+        }));
+        assert!(config.validate().is_ok());
+
+        let names: Vec<_> = config.all_locations().map(|(name, _)| name).collect();
+        assert_eq!(names, [DEFAULT_LOCATION, "Cabin-2", "office"]);
+    }
+
+    #[test]
+    fn invalid_location_names() {
+        // Names are used as URL path segments and directory names.
+        for name in [
+            "",
+            "../office",
+            "office/2",
+            "office.png",
+            "b\u{fc}ro",
+            "my office",
+        ] {
+            let config = config(serde_json::json!({ name: location() }));
+            assert!(config.validate().is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn duplicate_location_names() {
+        // The default location is served at `/image.{format}` and uses the configured
+        // directories as-is.
+        for name in [DEFAULT_LOCATION, "Default"] {
+            let config = config(serde_json::json!({ name: location() }));
+            assert!(config.validate().is_err(), "{name:?}");
+        }
+
+        let config = config(serde_json::json!({
+            "office": location(),
+            "Office": location(),
+        }));
+        assert!(config.validate().is_err());
+    }
 }
@@ -1,4 +1,4 @@
-use actix_web::error::ResponseError;
+use actix_web::{error::ResponseError, http::StatusCode};
 use epd_waveshare::graphics::VarDisplayError;
 use std::fmt::Display;
 
@@ -11,14 +11,20 @@ pub enum Error {
     Image(#[from] image::ImageError),
     #[error("Weather error: {0}")]
     Monsoon(#[from] monsoon::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
     #[error("Jiff error: {0}")]
     Jiff(#[from] jiff::Error),
     #[error("Config error: {0}")]
     Config(#[from] config::ConfigError),
     #[error("Prometheus error: {0}")]
     Prometheus(#[from] prometheus::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Bad request: {0}")]
+    BadRequest(String),
     #[error("{0}")]
     Message(String),
 }
@@ -29,7 +35,14 @@ impl Error {
     }
 }
 
-impl ResponseError for Error {}
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
 impl From<VarDisplayError> for Error {
     fn from(err: VarDisplayError) -> Self {
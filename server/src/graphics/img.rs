@@ -1,9 +1,9 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
 use embedded_graphics::prelude::*;
 use epd_waveshare::{
     buffer_len,
-    color::Color,
-    epd2in9_v2::{HEIGHT, WIDTH},
+    color::{Color, TriColor},
+    epd2in9_v2, epd2in9bc, epd2in13bc, epd4in2, epd7in5_v2,
     graphics::VarDisplay,
 };
 use image::{Pixel, Rgba, RgbaImage, imageops};
@@ -17,40 +17,255 @@ use tracing::trace;
 pub(super) const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
 pub(super) const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
 pub(super) const TRANSPARENT: Rgba<u8> = Rgba([0, 0, 0, 0]);
+// Tri-color displays show either red or yellow, the preview always uses red.
+pub(super) const ACCENT: Rgba<u8> = Rgba([255, 0, 0, 255]);
+pub(super) const LIGHT_GRAY: Rgba<u8> = Rgba([170, 170, 170, 255]);
+pub(super) const DARK_GRAY: Rgba<u8> = Rgba([85, 85, 85, 255]);
+
+// Height of the scene the sprites were drawn for, i.e. the 2.9" panel in landscape orientation.
+const BASE_HEIGHT: u32 = 128;
+
+/// A Waveshare e-paper display model to render images for.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Display {
+    /// 2.9" V2 panel with 128x296 pixels.
+    #[default]
+    Epd2in9V2,
+    /// 4.2" panel with 400x300 pixels.
+    Epd4in2,
+    /// 7.5" V2 panel with 800x480 pixels.
+    Epd7in5V2,
+    /// 2.9" B/C tri-color panel with 128x296 pixels.
+    Epd2in9Bc,
+    /// 2.13" B/C tri-color panel with 104x212 pixels.
+    Epd2in13Bc,
+}
+
+impl Display {
+    /// Returns width and height of the display in its native orientation, i.e. the orientation
+    /// of the frame buffer expected by the display controller.
+    pub fn native_size(self) -> (u32, u32) {
+        match self {
+            Display::Epd2in9V2 => (epd2in9_v2::WIDTH, epd2in9_v2::HEIGHT),
+            Display::Epd4in2 => (epd4in2::WIDTH, epd4in2::HEIGHT),
+            Display::Epd7in5V2 => (epd7in5_v2::WIDTH, epd7in5_v2::HEIGHT),
+            Display::Epd2in9Bc => (epd2in9bc::WIDTH, epd2in9bc::HEIGHT),
+            Display::Epd2in13Bc => (epd2in13bc::WIDTH, epd2in13bc::HEIGHT),
+        }
+    }
+
+    /// Returns `true` if the display can show an accent color besides black and white.
+    pub fn is_tri_color(self) -> bool {
+        matches!(self, Display::Epd2in9Bc | Display::Epd2in13Bc)
+    }
+
+    /// Returns `true` if the display supports 4-level grayscale.
+    pub fn is_grayscale(self) -> bool {
+        matches!(self, Display::Epd2in9V2)
+    }
+
+    /// Returns width and height of the canvas the scene is drawn on. The scene is always drawn in
+    /// landscape orientation.
+    pub fn canvas_size(self) -> (u32, u32) {
+        let (width, height) = self.native_size();
+        (width.max(height), width.min(height))
+    }
+
+    // Returns `true` if the display is in portrait orientation natively, so that the canvas needs
+    // to be rotated to match the frame buffer.
+    fn is_portrait(self) -> bool {
+        let (width, height) = self.native_size();
+        width < height
+    }
+}
+
+/// How the scene is arranged on the display.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// A single landscape scene, the timeline runs from left to right.
+    #[default]
+    Landscape,
+    /// For vertically mounted displays. The landscape scene is split in the middle of the
+    /// timeline and both halves are stacked on top of each other.
+    Portrait,
+}
+
+impl Layout {
+    // Returns width and height of the landscape scene drawn for `display`. In portrait layout,
+    // the scene is twice as wide and half as high, so that its halves stacked on top of each
+    // other fill the display.
+    fn scene_size(self, display: Display) -> (u32, u32) {
+        let (width, height) = display.canvas_size();
+
+        match self {
+            Layout::Landscape => (width, height),
+            Layout::Portrait => (height * 2, width / 2),
+        }
+    }
+}
 
 /// An image buffer that can be encoded in various formats.
 #[derive(Debug)]
-pub struct Image(RgbaImage);
+pub struct Image {
+    buf: RgbaImage,
+    display: Display,
+    grayscale: bool,
+    scale: u32,
+}
 
 impl Image {
-    pub(super) fn new(width: u32, height: u32) -> Self {
-        Image(RgbaImage::from_fn(width, height, |_, _| WHITE))
+    /// Creates a blank canvas to draw the scene for `display` in `layout`. Gray tones are only
+    /// drawn if `grayscale` is set.
+    ///
+    /// Everything is drawn `scale` times as large. Without a fixed scale, the largest factor for
+    /// which the scene still fits the display is used.
+    pub(super) fn new(
+        display: Display,
+        layout: Layout,
+        grayscale: bool,
+        scale: Option<u32>,
+    ) -> Self {
+        let (width, height) = layout.scene_size(display);
+        let scale = scale.unwrap_or((height / BASE_HEIGHT).max(1));
+
+        Image {
+            buf: RgbaImage::from_fn(width, height, |_, _| WHITE),
+            display,
+            grayscale,
+            scale,
+        }
+    }
+
+    /// Creates a blank canvas of `width` by `height` pixels for the default display, on which
+    /// everything is drawn `scale` times as large. Lets tests draw scenes in sizes of no display.
+    #[cfg(test)]
+    pub(super) fn with_size(width: u32, height: u32, scale: u32) -> Self {
+        Image {
+            buf: RgbaImage::from_fn(width, height, |_, _| WHITE),
+            display: Display::default(),
+            grayscale: false,
+            scale,
+        }
+    }
+
+    /// Returns the factor by which everything is drawn larger.
+    pub(super) fn scale(&self) -> u32 {
+        self.scale
     }
 
     pub(super) fn draw_pixel(&mut self, x: i64, y: i64) {
-        if x >= 0 && x < self.width() as i64 && y >= 0 && y < self.height() as i64 {
-            trace!("drawing pixel at ({x}, {y})");
-            self.0.put_pixel(x as u32, y as u32, BLACK);
+        self.draw_pixel_with(x, y, BLACK);
+    }
+
+    /// Draws a pixel of the scene, which is a square of `scale` pixels in size with its top left
+    /// corner at `x`/`y`.
+    pub(super) fn draw_pixel_with(&mut self, x: i64, y: i64, color: Rgba<u8>) {
+        trace!("drawing pixel at ({x}, {y})");
+        let scale = self.scale as i64;
+
+        for y in y..y + scale {
+            for x in x..x + scale {
+                if x >= 0 && x < self.width() as i64 && y >= 0 && y < self.height() as i64 {
+                    self.buf.put_pixel(x as u32, y as u32, color);
+                }
+            }
         }
     }
 
-    pub(super) fn invert_pixels(&mut self) {
+    /// Returns the color to highlight things with. That's black unless the display is tri-color.
+    pub(super) fn accent(&self) -> Rgba<u8> {
+        if self.display.is_tri_color() {
+            ACCENT
+        } else {
+            BLACK
+        }
+    }
+
+    /// Returns the light gray tone, or black if the image is not drawn in grayscale.
+    pub(super) fn light_gray(&self) -> Rgba<u8> {
+        if self.grayscale { LIGHT_GRAY } else { BLACK }
+    }
+
+    /// Returns the dark gray tone, or black if the image is not drawn in grayscale.
+    pub(super) fn dark_gray(&self) -> Rgba<u8> {
+        if self.grayscale { DARK_GRAY } else { BLACK }
+    }
+
+    /// Darkens white and light gray by one tone, so that the background turns light gray while
+    /// light gray things like fog stay visible. Does nothing unless the image is drawn in
+    /// grayscale.
+    pub(super) fn shade(&mut self) {
+        if !self.grayscale {
+            return;
+        }
+
         for pixel in self.pixels_mut() {
+            if *pixel == WHITE {
+                *pixel = LIGHT_GRAY;
+            } else if *pixel == LIGHT_GRAY {
+                *pixel = DARK_GRAY;
+            }
+        }
+    }
+
+    /// Inverts black and white, accents keep their color. Gray tones swap their lightness.
+    pub(super) fn invert_pixels(&mut self) {
+        for pixel in self.pixels_mut().filter(|pixel| **pixel != ACCENT) {
             pixel.invert();
         }
     }
 
+    /// Stacks the right half of the scene below the left half, turning it into a portrait image.
+    pub(super) fn stack(&mut self) {
+        let (width, height) = (self.width() / 2, self.height());
+        let mut buf = RgbaImage::new(width, height * 2);
+
+        for (x, y) in [(0, 0), (width, height)] {
+            let half = imageops::crop_imm(&self.buf, x, 0, width, height).to_image();
+            imageops::replace(&mut buf, &half, 0, y as i64);
+        }
+
+        self.buf = buf;
+    }
+
+    // Returns the image in the orientation of the display's frame buffer, rotating it if the
+    // orientations differ.
+    fn native_image(&self) -> RgbaImage {
+        let portrait = self.width() < self.height();
+
+        match (portrait, self.display.is_portrait()) {
+            (false, true) => imageops::rotate90(&self.buf),
+            (true, false) => imageops::rotate270(&self.buf),
+            _ => self.buf.clone(),
+        }
+    }
+
     fn encode_epd(&self) -> Result<Vec<u8>> {
-        // The image needs to be rotated for the e-paper display.
-        let image = imageops::rotate90(&self.0);
-        let buf_len = buffer_len(WIDTH as usize, HEIGHT as usize);
+        // One bit per pixel can't tell gray tones from black or white.
+        if self.grayscale {
+            return Err(Error::BadRequest(
+                "grayscale images can only be encoded as epd_gray or regular image formats"
+                    .to_owned(),
+            ));
+        }
+
+        let image = self.native_image();
+        let (width, height) = self.display.native_size();
+        let buf_len = buffer_len(width as usize, height as usize);
+
+        if self.display.is_tri_color() {
+            return Self::encode_tri_color(&image, width, height, buf_len);
+        }
+
         let mut buf = vec![Color::White.get_byte_value(); buf_len];
-        let mut display = VarDisplay::new(WIDTH, HEIGHT, &mut buf, false)?;
+        let mut display = VarDisplay::new(width, height, &mut buf, false)?;
 
         for (x, y, pixel) in image.enumerate_pixels() {
             let point = Point::new(x as i32, y as i32);
 
-            if *pixel == BLACK {
+            if *pixel == BLACK || *pixel == ACCENT {
                 display.set_pixel(Pixel(point, Color::Black));
             } else {
                 display.set_pixel(Pixel(point, Color::White));
@@ -60,9 +275,71 @@ impl Image {
         Ok(buf)
     }
 
+    // Tri-color displays expect two bit-planes, one for black and white followed by one for the
+    // accent color.
+    fn encode_tri_color(
+        image: &RgbaImage,
+        width: u32,
+        height: u32,
+        buf_len: usize,
+    ) -> Result<Vec<u8>> {
+        let mut buf = vec![0; buf_len * 2];
+        let mut display = VarDisplay::<TriColor>::new(width, height, &mut buf, false)?;
+
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let point = Point::new(x as i32, y as i32);
+
+            let color = if *pixel == BLACK {
+                TriColor::Black
+            } else if *pixel == ACCENT {
+                TriColor::Chromatic
+            } else {
+                TriColor::White
+            };
+
+            display.set_pixel(Pixel(point, color));
+        }
+
+        Ok(buf)
+    }
+
+    // Packs four pixels into each byte, starting with the most significant bits. The two bits of
+    // a pixel are 0 for black, 1 for dark gray, 2 for light gray and 3 for white.
+    fn encode_epd_gray(&self) -> Result<Vec<u8>> {
+        if !self.display.is_grayscale() {
+            return Err(Error::BadRequest(format!(
+                "display {:?} does not support grayscale",
+                self.display
+            )));
+        }
+
+        let image = self.native_image();
+        let (width, height) = self.display.native_size();
+        let row_len = (width as usize).div_ceil(4);
+        let mut buf = vec![0xff; row_len * height as usize];
+
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let level = if *pixel == BLACK || *pixel == ACCENT {
+                0
+            } else if *pixel == DARK_GRAY {
+                1
+            } else if *pixel == LIGHT_GRAY {
+                2
+            } else {
+                3
+            };
+
+            let index = y as usize * row_len + x as usize / 4;
+            let shift = 6 - (x % 4) * 2;
+            buf[index] = (buf[index] & !(0b11 << shift)) | (level << shift);
+        }
+
+        Ok(buf)
+    }
+
     fn encode_as(&self, format: image::ImageFormat) -> Result<Vec<u8>> {
         let mut buf: Vec<u8> = Vec::new();
-        self.0.write_to(&mut Cursor::new(&mut buf), format)?;
+        self.buf.write_to(&mut Cursor::new(&mut buf), format)?;
         Ok(buf)
     }
 
@@ -71,6 +348,7 @@ impl Image {
     pub fn encode(&self, format: ImageFormat) -> Result<(Vec<u8>, mime::Mime)> {
         let bytes = match format {
             ImageFormat::Epd => self.encode_epd()?,
+            ImageFormat::EpdGray => self.encode_epd_gray()?,
             ImageFormat::Png => self.encode_as(image::ImageFormat::Png)?,
             ImageFormat::Gif => self.encode_as(image::ImageFormat::Gif)?,
             ImageFormat::Bmp => self.encode_as(image::ImageFormat::Bmp)?,
@@ -83,13 +361,13 @@ impl Deref for Image {
     type Target = RgbaImage;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.buf
     }
 }
 
 impl DerefMut for Image {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.buf
     }
 }
 
@@ -99,6 +377,8 @@ impl DerefMut for Image {
 pub enum ImageFormat {
     /// Raw bytes for an E-paper display.
     Epd,
+    /// Raw bytes with two bits per pixel for an E-paper display in 4-level grayscale mode.
+    EpdGray,
     /// PNG image.
     Png,
     /// GIF image.
@@ -111,10 +391,62 @@ impl ImageFormat {
     /// Returns a MIME type suitable for serving the encoded image bytes.
     pub fn mime_type(&self) -> mime::Mime {
         match self {
-            ImageFormat::Epd => mime::APPLICATION_OCTET_STREAM,
+            ImageFormat::Epd | ImageFormat::EpdGray => mime::APPLICATION_OCTET_STREAM,
             ImageFormat::Png => mime::IMAGE_PNG,
             ImageFormat::Gif => mime::IMAGE_GIF,
             ImageFormat::Bmp => mime::IMAGE_BMP,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stack() {
+        let mut img = Image::new(Display::Epd4in2, Layout::Portrait, false, None);
+        let (width, height) = img.dimensions();
+        assert_eq!((width, height), (600, 200));
+
+        // Mark the top left corner of both halves.
+        img.put_pixel(0, 0, BLACK);
+        img.put_pixel(width / 2, 0, ACCENT);
+
+        // The left half stays on top, the right half goes below it.
+        img.stack();
+        assert_eq!(img.dimensions(), (300, 400));
+        assert_eq!(*img.get_pixel(0, 0), BLACK);
+        assert_eq!(*img.get_pixel(0, height), ACCENT);
+        assert_eq!(img.pixels().filter(|&&p| p != WHITE).count(), 2);
+    }
+
+    #[test]
+    fn native_image() {
+        // Landscape scene on a panel which is portrait natively, turned clockwise.
+        let mut img = Image::new(Display::Epd2in9V2, Layout::Landscape, false, None);
+        img.put_pixel(0, 0, BLACK);
+
+        let native = img.native_image();
+        assert_eq!(native.dimensions(), (128, 296));
+        assert_eq!(*native.get_pixel(127, 0), BLACK);
+
+        // Portrait scene on a panel which is landscape natively, turned counter-clockwise.
+        let mut img = Image::new(Display::Epd4in2, Layout::Portrait, false, None);
+        img.stack();
+        img.put_pixel(0, 0, BLACK);
+
+        let native = img.native_image();
+        assert_eq!(native.dimensions(), (400, 300));
+        assert_eq!(*native.get_pixel(0, 299), BLACK);
+
+        // No rotation if the orientations match.
+        let mut img = Image::new(Display::Epd2in9V2, Layout::Portrait, false, None);
+        img.stack();
+        img.put_pixel(0, 0, BLACK);
+
+        let native = img.native_image();
+        assert_eq!(native.dimensions(), (128, 296));
+        assert_eq!(*native.get_pixel(0, 0), BLACK);
+    }
+}
@@ -1,86 +1,166 @@
 mod img;
 mod sprites;
 
-pub use self::img::{Image, ImageFormat};
+pub use self::img::{Display, Image, ImageFormat, Layout};
+pub use self::sprites::{load_sprites, watch_sprites};
 use self::{
     img::{BLACK, TRANSPARENT, WHITE},
-    sprites::{Sprite, sprite, spriten},
+    sprites::{Sprite, scaled_sprite, sprite},
 };
 use crate::{
     app::Metrics,
-    config::Config,
-    sun::{Sun, SunPhase::*},
-    weather::{Condition, DataPoint, WeatherData},
+    config::{Config, Units},
+    error::Result,
+    moon::{Moon, MoonPhase},
+    sun::{
+        Daylight, Sun,
+        SunPhase::{self, *},
+    },
+    weather::{Alert, Condition, DataPoint, Intensity, Severity, WeatherData},
 };
-use epd_waveshare::epd2in9_v2::{HEIGHT, WIDTH};
+use image::Rgba;
 use imageproc::drawing::BresenhamLineIter;
-use jiff::{SignedDuration, Timestamp, civil::time, tz::TimeZone};
+use jiff::{SignedDuration, Timestamp, tz::TimeZone};
 use rand::{
     Rng,
     rngs::StdRng,
     seq::{IndexedRandom, SliceRandom},
 };
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::f64::consts::PI;
+use std::sync::Arc;
 use tracing::debug;
 
 /// Renders landscape images from weather data.
 #[derive(Clone)]
 pub struct Renderer {
     night_mode: bool,
+    display: Display,
+    layout: Layout,
+    // A fixed scale factor, derived from the display size if `None`.
+    scale: Option<u32>,
+    units: Units,
+    // The number of past hours shown left of the house, zero if there is no history.
+    history_hours: i64,
+    time_zone: TimeZone,
     metrics: Metrics,
 }
 
 impl Renderer {
-    /// Creates a new `Renderer` from config and metrics.
-    pub fn new(config: &Config, metrics: Metrics) -> Self {
+    /// Creates a new `Renderer` from config and metrics. Civil times, like midnight, are
+    /// calculated in `time_zone`.
+    pub fn new(config: &Config, time_zone: TimeZone, metrics: Metrics) -> Self {
         Renderer {
             night_mode: !config.disable_night_mode,
+            display: config.display,
+            layout: config.layout,
+            scale: config.scale,
+            units: config.units,
+            history_hours: if config.history.enabled {
+                config.history.hours as i64
+            } else {
+                0
+            },
+            time_zone,
             metrics,
         }
     }
 
-    /// Renders the weather data into a landscape image.
-    pub fn render(&self, data: &WeatherData, rng: StdRng) -> Image {
-        let mut ctx = RenderContext::new(data, rng);
+    /// Renders the weather data into an image showing the scene at `instant`. The image is sized
+    /// for `display` and arranged in `layout` if given, and according to the config otherwise.
+    /// Fog, distant clouds and twilight are shaded in gray if `grayscale` is set.
+    ///
+    /// The image only depends on the arguments, so rendering the same data at the same instant
+    /// with an equally seeded `rng` always yields the same image.
+    pub fn render(
+        &self,
+        data: &WeatherData,
+        instant: Timestamp,
+        display: Option<Display>,
+        layout: Option<Layout>,
+        grayscale: bool,
+        rng: StdRng,
+    ) -> Result<Image> {
+        let display = display.unwrap_or(self.display);
+        let layout = layout.unwrap_or(self.layout);
+
+        // Forecasts are placed by their index, so stale data must not start in the past. Otherwise
+        // past hours would be drawn as current while the sun, moon and alerts are drawn at their
+        // actual times.
+        let mut data = data.clone();
+        data.trim_to(instant);
+
+        // The scene is always drawn in landscape mode, because it's more intiutive. It gets
+        // rearranged for the portrait layout afterwards, and rotated when encoding it for displays
+        // whose orientation differs.
+        let img = Image::new(display, layout, grayscale, self.scale);
+        let mut img = self.draw_scene(&data, instant, img, rng)?;
+
+        if layout == Layout::Portrait {
+            img.stack();
+        }
+
+        Ok(img)
+    }
+
+    // Draws the landscape scene for `data` at `instant` onto `img`.
+    fn draw_scene(
+        &self,
+        data: &WeatherData,
+        instant: Timestamp,
+        img: Image,
+        rng: StdRng,
+    ) -> Result<Image> {
+        let mut ctx = RenderContext::new(data, instant, img, rng, self.history_hours)?;
 
         debug!(?data, "rendering image for weather data");
 
-        self.draw_celestial_bodies(&mut ctx);
-        self.draw_current_weather(&mut ctx, &data.current);
-        self.draw_forecasts(&mut ctx, &data.forecasts);
-        self.draw_midday_and_midnight(&mut ctx);
+        self.draw_celestial_bodies(&mut ctx)?;
+        self.draw_history(&mut ctx, data);
+        self.draw_current_weather(&mut ctx, &data.current)?;
+        self.draw_forecasts(&mut ctx, &data.forecasts)?;
+        self.draw_midday_and_midnight(&mut ctx)?;
+        self.draw_alerts(&mut ctx, &data.alerts)?;
+
+        if data.stale {
+            let age = ctx.instant.duration_since(data.fetched_at);
+            self.draw_staleness(&mut ctx, age)?;
+        }
 
         // Draw the temperature graph.
         for (x, y) in ctx.temperature_graph {
             ctx.img.draw_pixel(x, y);
         }
 
-        let dark_outside = !ctx.sun.is_between(ctx.instant, Dawn, Dusk);
-
-        if self.night_mode && dark_outside {
+        if self.night_mode && ctx.sun.is_dark(ctx.instant) {
             ctx.img.invert_pixels();
+        } else if self.night_mode && ctx.sun.is_twilight(ctx.instant) {
+            ctx.img.shade();
         }
 
-        ctx.img
+        Ok(ctx.img)
     }
 
-    fn draw_house(&self, ctx: &mut RenderContext, weather: &DataPoint) {
-        let twilight = ctx.sun.is_between(ctx.instant, Sunset, Night)
-            || ctx.sun.is_between(ctx.instant, NightEnd, Sunrise);
-
-        let house = if twilight {
+    fn draw_house(&self, ctx: &mut RenderContext, weather: &DataPoint) -> Result<()> {
+        let house = if ctx.sun.is_twilight(ctx.instant) {
             // It's dark outside, lights on.
-            sprite("house_01")
+            ctx.sprite("house_01")?
         } else {
             // It's either day time or late at night, lights out in any case.
-            sprite("house_00")
+            ctx.sprite("house_00")?
         };
 
         let y = ctx.temperature_to_y(weather.air_temperature);
 
-        self.draw_sprite(ctx, house, 0, y - house.height() as i64);
-        self.draw_smoke(ctx, weather, 21, ctx.img.height() as i64 - y + 22);
+        self.draw_sprite(ctx, &house, ctx.house_x, y - house.height() as i64);
+        self.draw_smoke(
+            ctx,
+            weather,
+            ctx.house_x + 21 * ctx.scale,
+            ctx.img.height() as i64 - y + 22 * ctx.scale,
+        );
+
+        Ok(())
     }
 
     fn draw_smoke(&self, ctx: &mut RenderContext, weather: &DataPoint, x0: i64, y0: i64) {
@@ -129,8 +209,11 @@ impl Renderer {
 
         let width = ctx.img.width() as i64;
         let height = ctx.img.height() as i64;
+        let scale = ctx.scale;
 
-        for (x, y, r) in make_smoke(angle, width, height) {
+        // The smoke is calculated in unscaled pixels, so that it has the same shape at every
+        // scale.
+        for (x, y, r) in make_smoke(angle, width / scale, height / scale) {
             if ctx.rng.random::<f64>() * 1.3 > r {
                 let (dx, dy) = if ctx.rng.random::<f64>() * 1.2 < r {
                     (ctx.rng.random_range(-1..=1), ctx.rng.random_range(-1..=1))
@@ -138,73 +221,170 @@ impl Renderer {
                     (0, 0)
                 };
 
-                ctx.img.draw_pixel(x0 + x + dx, height - (y0 + y) + dy);
+                ctx.img.draw_pixel(
+                    x0 + (x + dx) * scale,
+                    height - (y0 + y * scale) + dy * scale,
+                );
             }
         }
     }
 
-    fn draw_celestial_bodies(&self, ctx: &mut RenderContext) {
-        let sun = sprite("sun_00");
-        let next_sunrise = ctx.sun.next_phase(ctx.instant, Sunrise);
-        let sun_x = ctx.timestamp_to_x(next_sunrise) - (sun.width() / 2) as i64;
+    fn draw_celestial_bodies(&self, ctx: &mut RenderContext) -> Result<()> {
+        let sun = ctx.sprite("sun_00")?;
+
+        if ctx.sun.daylight(ctx.instant) == Daylight::PolarDay {
+            // The sun does not rise because it never set, so we'll show it right away.
+            let sun_x = ctx.x_offset - (sun.width() / 2) as i64;
+            self.draw_sprite(ctx, &sun, sun_x, 0);
+        }
+
+        for sunrise in ctx.sun_phases(Sunrise) {
+            let sun_x = ctx.timestamp_to_x(sunrise) - (sun.width() / 2) as i64;
+            self.draw_sprite(ctx, &sun, sun_x, 0);
+        }
 
-        self.draw_sprite(ctx, sun, sun_x, 0);
+        for moonrise in ctx.moonrises() {
+            let illumination = ctx.moon.illumination(moonrise);
 
-        let moon = sprite("moon_00");
-        let next_sunset = ctx.sun.next_phase(ctx.instant, Sunset);
-        let moon_x = ctx.timestamp_to_x(next_sunset) - (moon.width() / 4) as i64;
+            let moon = if illumination.phase() == MoonPhase::New {
+                // There's nothing to see during new moon.
+                continue;
+            } else if illumination.fraction < 0.5 {
+                ctx.sprite("moon_00")?
+            } else {
+                ctx.sprite("moon_01")?
+            };
 
-        self.draw_sprite(ctx, moon, moon_x, 0);
+            let moon_x = ctx.timestamp_to_x(moonrise) - (moon.width() / 4) as i64;
+            self.draw_sprite(ctx, &moon, moon_x, 0);
+        }
+
+        Ok(())
+    }
+
+    fn draw_midday_and_midnight(&self, ctx: &mut RenderContext) -> Result<()> {
+        self.draw_flower(ctx, "flower_00", 0)?;
+        self.draw_flower(ctx, "flower_01", 12)
+    }
+
+    fn draw_flower(&self, ctx: &mut RenderContext, name: &str, hour: i8) -> Result<()> {
+        // Depending on the forecast horizon, the hour may occur more than once.
+        for ts in local_times(ctx.instant, ctx.horizon_end(), hour, &self.time_zone) {
+            let x = ctx.timestamp_to_x(ts);
+
+            if x < ctx.x_offset {
+                // We don't want it to overlap with the house, or do we?
+                continue;
+            }
+
+            if let Some(&y) = ctx.temperature_graph.get(&x) {
+                let sprite = ctx.sprite(name)?;
+                let y = y - sprite.height() as i64;
+                self.draw_sprite(ctx, &sprite, x, y);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_sky(
+        &self,
+        ctx: &mut RenderContext,
+        data: &DataPoint,
+        x: i64,
+        width: i64,
+    ) -> Result<()> {
+        let scale = ctx.scale;
+        self.draw_clouds(ctx, data, x, 5 * scale, width)?;
+        self.draw_precipitation(ctx, data, x, ctx.cloud_height + 5 * scale, width);
+        self.draw_fog(ctx, data, x, ctx.cloud_height + 10 * scale, width);
+        Ok(())
     }
 
-    fn draw_midday_and_midnight(&self, ctx: &mut RenderContext) {
-        self.draw_flower(ctx, "flower_00", 0);
-        self.draw_flower(ctx, "flower_01", 12);
+    fn draw_current_weather(&self, ctx: &mut RenderContext, weather: &DataPoint) -> Result<()> {
+        self.draw_house(ctx, weather)?;
+        let house_width = ctx.x_offset - ctx.house_x;
+        self.draw_sky(ctx, weather, ctx.house_x, house_width)?;
+        let x = self.draw_temperature(
+            ctx,
+            weather.air_temperature,
+            ctx.house_x + house_width / 2,
+            false,
+        )?;
+
+        // Show the unit next to the current temperature only, it'll get too crowded otherwise.
+        let y = ctx.temperature_to_y(weather.air_temperature) + 5 * ctx.scale;
+        let unit = match self.units {
+            Units::Metric => ctx.sprite("unit_00")?,
+            Units::Imperial => ctx.sprite("unit_01")?,
+        };
+        self.draw_sprite(ctx, &unit, x + ctx.scale, y);
+        Ok(())
     }
 
-    fn draw_flower(&self, ctx: &mut RenderContext, name: &str, hour: i8) {
-        let local_time = ctx.instant.to_zoned(TimeZone::system());
-        let mut time = local_time.with().time(time(hour, 0, 0, 0)).build().unwrap();
-        if time < local_time {
-            time = time.checked_add(SignedDuration::from_hours(24)).unwrap();
+    // Draws the observed temperatures of the past hours as a dotted line left of the house, so
+    // that it's not mistaken for the forecast.
+    fn draw_history(&self, ctx: &mut RenderContext, data: &WeatherData) {
+        if ctx.history_hours == 0 {
+            return;
         }
 
-        let x = ctx.timestamp_to_x(time.timestamp());
+        let start = ctx.history_start();
+        let height = ctx.img.height() as i64;
+
+        let mut points: Vec<(i64, i64)> = data
+            .history
+            .iter()
+            .filter(|o| o.timestamp >= start && o.timestamp < data.current.timestamp)
+            .map(|o| {
+                (
+                    ctx.history_x(o.timestamp),
+                    ctx.temperature_to_y(o.air_temperature),
+                )
+            })
+            .collect();
 
-        if x < ctx.x_offset {
-            // We don't want it to overlap with the house, or do we?
+        if points.is_empty() {
             return;
         }
 
-        if let Some(&y) = ctx.temperature_graph.get(&x) {
-            let sprite = sprite(name);
-            let y = y - sprite.height() as i64;
-            self.draw_sprite(ctx, sprite, x, y);
+        // Connect the observations to the current temperature below the house.
+        points.push((
+            ctx.house_x,
+            ctx.temperature_to_y(data.current.air_temperature),
+        ));
+
+        for window in points.windows(2) {
+            let ((x1, y1), (x2, y2)) = (window[0], window[1]);
+            let (start, end) = ((x1 as f32, y1 as f32), (x2 as f32, y2 as f32));
+
+            for (x, y) in BresenhamLineIter::new(start, end) {
+                let (x, y) = (x as i64, y as i64);
+
+                // Past temperatures may be outside the range of the forecast.
+                if (x / ctx.scale) % 2 == 0 {
+                    ctx.img.draw_pixel(x, y.clamp(0, height - ctx.scale));
+                }
+            }
         }
-    }
 
-    fn draw_sky(&self, ctx: &mut RenderContext, data: &DataPoint, x: i64, width: i64) {
-        self.draw_clouds(ctx, data, x, 5, width);
-        self.draw_precipitation(ctx, data, x, ctx.cloud_height + 5, width);
-        self.draw_fog(ctx, data, x, ctx.cloud_height + 10, width);
+        self.metrics.object_counter("history").inc();
     }
 
-    fn draw_current_weather(&self, ctx: &mut RenderContext, weather: &DataPoint) {
-        self.draw_house(ctx, weather);
-        self.draw_sky(ctx, weather, 0, ctx.x_offset);
-        self.draw_temperature(ctx, weather.air_temperature, ctx.x_offset / 2);
-    }
+    fn draw_forecasts(&self, ctx: &mut RenderContext, forecasts: &[DataPoint]) -> Result<()> {
+        // Only draw six forecast samples, e.g. one for every 4 hours of a 24 hour forecast. It'll
+        // get too crowded otherwise.
+        let step = (forecasts.len() / 6).max(1);
+        let width = (ctx.x_step * step as f64).round() as i64;
 
-    fn draw_forecasts(&self, ctx: &mut RenderContext, forecasts: &[DataPoint]) {
-        // Only draw a forecast sample for every 4 hours. It'll get too crowded otherwise.
-        for (i, forecast) in forecasts.iter().enumerate().step_by(4) {
+        for (i, forecast) in forecasts.iter().enumerate().step_by(step) {
             let x = ctx.forecast_x(i);
-            self.draw_sky(ctx, forecast, x, ctx.x_step * 4);
-            self.draw_trees(ctx, forecast, x);
+            self.draw_sky(ctx, forecast, x, width)?;
+            self.draw_trees(ctx, forecast, x)?;
         }
 
-        self.draw_temperature_extrema(ctx, forecasts, ctx.min_temperature);
-        self.draw_temperature_extrema(ctx, forecasts, ctx.max_temperature);
+        self.draw_temperature_extrema(ctx, forecasts, ctx.min_temperature)?;
+        self.draw_temperature_extrema(ctx, forecasts, ctx.max_temperature)
     }
 
     fn draw_temperature_extrema(
@@ -212,23 +392,42 @@ impl Renderer {
         ctx: &mut RenderContext,
         forecasts: &[DataPoint],
         temperature: f64,
-    ) {
+    ) -> Result<()> {
         if let Some((i, data_point)) = forecasts
             .iter()
             .enumerate()
             .find(|(_, dp)| dp.air_temperature == temperature)
         {
             let x = ctx.forecast_x(i);
-            self.draw_temperature(ctx, data_point.air_temperature, x);
+            self.draw_temperature(ctx, data_point.air_temperature, x, true)?;
         }
+
+        Ok(())
     }
 
-    fn draw_temperature(&self, ctx: &mut RenderContext, temperature: f64, x: i64) {
+    // Draws the temperature given in degrees Celsius below the temperature graph. The position is
+    // always based on degrees Celsius, so that the graph looks the same in every unit system.
+    // Returns the x-coordinate right after the last digit.
+    fn draw_temperature(
+        &self,
+        ctx: &mut RenderContext,
+        temperature: f64,
+        x: i64,
+        accent: bool,
+    ) -> Result<i64> {
         let y = ctx.temperature_to_y(temperature);
-        self.draw_number(ctx, x, y + 5, temperature.round() as i64);
+        let value = self.units.temperature(temperature).round() as i64;
+        self.draw_number(ctx, x, y + 5 * ctx.scale, value, accent)
     }
 
-    fn draw_clouds(&self, ctx: &mut RenderContext, data: &DataPoint, x: i64, y: i64, width: i64) {
+    fn draw_clouds(
+        &self,
+        ctx: &mut RenderContext,
+        data: &DataPoint,
+        x: i64,
+        y: i64,
+        width: i64,
+    ) -> Result<()> {
         let cloud_set: &[usize] = match data.cloud_area_fraction {
             2.0..5.0 => &[2],
             5.0..10.0 => &[3, 2],
@@ -247,11 +446,20 @@ impl Renderer {
         for &n in cloud_set {
             let offset = ctx.rng.random_range(0..width);
 
-            self.draw_lightning(ctx, data, x + offset, ctx.cloud_height + y - 1, n);
+            let lightning_y = ctx.cloud_height + y - ctx.scale;
+            self.draw_lightning(ctx, data, x + offset, lightning_y, n)?;
+
+            let cloud = ctx.spriten("cloud", n)?;
 
-            let cloud = spriten("cloud", n);
-            self.draw_sprite(ctx, cloud, x + offset, y);
+            if ctx.is_distant(x) {
+                let color = ctx.img.dark_gray();
+                self.draw_sprite_with(ctx, &cloud, x + offset, y, color);
+            } else {
+                self.draw_sprite(ctx, &cloud, x + offset, y);
+            }
         }
+
+        Ok(())
     }
 
     fn draw_lightning(
@@ -261,10 +469,18 @@ impl Renderer {
         x: i64,
         y: i64,
         cloud_n: usize,
-    ) {
-        if data.probability_of_thunder <= 0.0 {
+    ) -> Result<()> {
+        // Thunder in the symbol code means that a thunderstorm is expected, even if the
+        // probability of thunder is low or unknown.
+        let probability_of_thunder = if data.condition.thunder() {
+            data.probability_of_thunder.max(0.5)
+        } else {
+            data.probability_of_thunder
+        };
+
+        if probability_of_thunder <= 0.0 {
             // There's no thunderstorm that could spit lightnings.
-            return;
+            return Ok(());
         }
 
         // The offsets shift the lightnings roughly centered below the cloud.
@@ -278,12 +494,15 @@ impl Renderer {
             _ => (&[0], 0),
         };
 
-        if ctx.rng.random_bool(data.probability_of_thunder) {
+        if ctx.rng.random_bool(probability_of_thunder.min(1.0)) {
             if let Some(&n) = lightning_set.choose(&mut ctx.rng) {
-                let lightning = spriten("lightning", n);
-                self.draw_sprite(ctx, lightning, x + lightning_offset, y);
+                let lightning = ctx.spriten("lightning", n)?;
+                let x = x + lightning_offset * ctx.scale;
+                self.draw_accent_sprite(ctx, &lightning, x, y);
             }
         }
+
+        Ok(())
     }
 
     fn draw_fog(&self, ctx: &mut RenderContext, data: &DataPoint, x: i64, y: i64, width: i64) {
@@ -295,8 +514,12 @@ impl Renderer {
             return;
         };
 
+        let scale = ctx.scale;
         let fog_width = width / 2;
-        let y_step = 6;
+        // Hours get narrow if many are shown, leaving no room to vary the start of the fog.
+        let max_offset = (fog_width / 2).max(3 * scale + 1);
+        let color = ctx.img.light_gray();
+        let y_step = 6 * scale as usize;
         let y_range = (y_max - y) / 2;
 
         for y_off in (0..y_range).step_by(y_step) {
@@ -306,14 +529,14 @@ impl Renderer {
                 break;
             }
 
-            let x_start = x + ctx.rng.random_range(3..fog_width / 2);
+            let x_start = x + ctx.rng.random_range(3 * scale..max_offset);
             let y_start = y + y_off;
 
-            for i in 0..=fog_width {
+            for i in (0..=fog_width).step_by(scale as usize) {
                 let x = x_start + i;
-                let y = y_start + (i as f64 + 2.0).sin().round() as i64;
+                let y = y_start + ((i / scale) as f64 + 2.0).sin().round() as i64 * scale;
 
-                ctx.img.draw_pixel(x, y);
+                ctx.img.draw_pixel_with(x, y, color);
             }
 
             self.metrics.object_counter("fog").inc();
@@ -334,20 +557,36 @@ impl Renderer {
         }
 
         let (heaviness, factor) = match data.condition {
-            Condition::Snow => (5.0, 10.0),
-            Condition::Sleet => (5.0, 15.0),
+            Condition::Snow(_) => (5.0, 10.0),
+            Condition::Sleet(_) => (5.0, 15.0),
             _ => (5.0, 20.0),
         };
 
-        let r = 1.0 - (data.precipitation_amount / heaviness) / factor;
+        let precipitation = data.condition.precipitation().unwrap_or_default();
+
+        // The intensity from the symbol code makes light precipitation look sparser and heavy
+        // precipitation denser than the amount alone would.
+        let (density, drop_length) = match precipitation.intensity {
+            Intensity::Light => (0.5, 2),
+            Intensity::Normal => (1.0, 2),
+            Intensity::Heavy => (1.5, 3),
+        };
+
+        let r = 1.0 - density * (data.precipitation_amount / heaviness) / factor;
+        let scale = ctx.scale;
+
+        for x in (x..x + width).step_by(scale as usize) {
+            // Showers come down in streaks with gaps in between.
+            if precipitation.showers && (x / (4 * scale)) % 2 == 1 {
+                continue;
+            }
 
-        for x in x..x + width {
             if let Some(&y_max) = ctx.temperature_graph.get(&x) {
-                for y in (y..y_max).step_by(2) {
+                for y in (y..y_max).step_by(2 * scale as usize) {
                     if ctx.rng.random::<f64>() > r {
                         let snow = match data.condition {
-                            Condition::Snow => true,
-                            Condition::Sleet => ctx.rng.random(),
+                            Condition::Snow(_) => true,
+                            Condition::Sleet(_) => ctx.rng.random(),
                             _ => false,
                         };
 
@@ -355,8 +594,9 @@ impl Renderer {
                             ctx.img.draw_pixel(x, y);
                             self.metrics.object_counter("snowflake").inc();
                         } else {
-                            ctx.img.draw_pixel(x, y);
-                            ctx.img.draw_pixel(x, y - 1);
+                            for i in 0..drop_length {
+                                ctx.img.draw_pixel(x, y - i * scale);
+                            }
                             self.metrics.object_counter("raindrop").inc();
                         }
                     }
@@ -365,7 +605,7 @@ impl Renderer {
         }
     }
 
-    fn draw_trees(&self, ctx: &mut RenderContext, data: &DataPoint, x: i64) {
+    fn draw_trees(&self, ctx: &mut RenderContext, data: &DataPoint, x: i64) -> Result<()> {
         // @FIXME(mohmann): Simplify this complicated method.
 
         fn direction_distance(a: f64, b: f64) -> f64 {
@@ -433,72 +673,241 @@ impl Renderer {
         let mut x_offset = x;
 
         for (tree_index, &wind_index) in wind_indices.into_iter().enumerate() {
-            let offset = x_offset + 5;
+            let offset = x_offset + 5 * ctx.scale;
 
             if offset > ctx.temperature_graph.len() as i64 {
                 break;
             }
 
             if let Some(name) = trees.get(tree_index) {
-                let Some(y) = ctx.temperature_graph.get(&offset) else {
+                let Some(&y) = ctx.temperature_graph.get(&offset) else {
                     continue;
                 };
-                let tree = spriten(name, wind_index);
-                let y_offset = (y - tree.height() as i64) + 1;
-                self.draw_sprite(ctx, tree, x_offset, y_offset);
+                let tree = ctx.spriten(name, wind_index)?;
+                let y_offset = (y - tree.height() as i64) + ctx.scale;
+                self.draw_sprite(ctx, &tree, x_offset, y_offset);
             }
 
-            x_offset += 9;
+            x_offset += 9 * ctx.scale;
         }
+
+        Ok(())
     }
 
-    fn draw_number(&self, ctx: &mut RenderContext, x: i64, y: i64, value: i64) {
+    fn draw_alerts(&self, ctx: &mut RenderContext, alerts: &[Alert]) -> Result<()> {
+        let scale = ctx.scale;
+        let y = ctx.img.height() as i64 - scale;
+
+        for alert in alerts {
+            let start = alert.onset.max(ctx.instant);
+            let end = alert.expires.min(ctx.horizon_end());
+
+            if start >= end {
+                // The alert is not in effect within the forecast horizon.
+                continue;
+            }
+
+            let x_start = ctx.timestamp_to_x(start);
+            let x_end = ctx.timestamp_to_x(end);
+
+            // Alerts which are already in effect are highlighted.
+            let active = alert.onset <= ctx.instant;
+            let color = if active { ctx.img.accent() } else { BLACK };
+
+            // Mark the affected hours with a dotted line along the bottom of the image.
+            for x in (x_start..x_end).step_by(2 * scale as usize) {
+                ctx.img.draw_pixel_with(x, y, color);
+            }
+
+            // The number of exclamation marks on the sign shows the severity.
+            let n = match alert.severity {
+                Severity::Extreme => 2,
+                Severity::Severe => 1,
+                _ => 0,
+            };
+
+            let warning = ctx.spriten("warning", n)?;
+            let y = y - warning.height() as i64 - scale;
+
+            if active {
+                self.draw_accent_sprite(ctx, &warning, x_start, y);
+            } else {
+                self.draw_sprite(ctx, &warning, x_start, y);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Draws `value` with its sign, centered around `x`, in the accent color if `accent` is set.
+    // Returns the x-coordinate right after the last digit.
+    fn draw_number(
+        &self,
+        ctx: &mut RenderContext,
+        x: i64,
+        y: i64,
+        value: i64,
+        accent: bool,
+    ) -> Result<i64> {
+        let draw = |ctx: &mut RenderContext, sprite: &Sprite, x: i64| {
+            if accent {
+                self.draw_accent_sprite(ctx, sprite, x, y);
+            } else {
+                self.draw_sprite(ctx, sprite, x, y);
+            }
+        };
+
         let sign = if value >= 0 {
-            sprite("digit_10") // plus
+            ctx.sprite("digit_10")? // plus
         } else {
-            sprite("digit_11") // minus
+            ctx.sprite("digit_11")? // minus
         };
 
-        // We're assuming that air temperatures values have at most 2 digits, anything else would
-        // be highly concerning.
-        let value = value.abs();
-        let d1 = value / 10;
-        let d2 = value % 10;
+        // Air temperatures have three digits at most, e.g. 104 in degrees Fahrenheit.
+        let mut digits = Vec::new();
+        let mut rest = value.unsigned_abs();
+
+        loop {
+            digits.push(rest % 10);
+            rest /= 10;
+
+            if rest == 0 {
+                break;
+            }
+        }
 
-        let digits = if value < 10 { 1 } else { 2 };
         let digit_width = sign.width() as i64;
+        let spacing = ctx.scale;
 
         // Center the digits, excluding the sign because it looks better.
-        let mut offset = -(digits * (digit_width + 1) / 2) - digit_width;
+        let mut offset = -(digits.len() as i64 * (digit_width + spacing) / 2) - digit_width;
+
+        draw(ctx, &sign, x + offset);
+        offset += digit_width + spacing;
+
+        for &d in digits.iter().rev() {
+            let digit = ctx.spriten("digit", d as _)?;
+            draw(ctx, &digit, x + offset);
+            offset += digit_width + spacing;
+        }
+
+        Ok(x + offset - spacing)
+    }
+
+    fn draw_staleness(&self, ctx: &mut RenderContext, age: SignedDuration) -> Result<()> {
+        // Round up, so that data which is stale for less than an hour shows up as one hour old.
+        let hours = (age.as_secs_f64() / 3600.0).ceil().max(1.0) as u64;
+        let digits = hours.to_string();
+
+        let scale = ctx.scale;
+        let digit = ctx.sprite("digit_00")?;
+        let digit_width = digit.width() as i64;
+        let digit_height = digit.height() as i64;
+
+        // Draw the age into a frame in the top left corner.
+        let width = digits.len() as i64 * (digit_width + scale) + 3 * scale;
+        let height = digit_height + 4 * scale;
 
-        self.draw_sprite(ctx, sign, x + offset, y);
-        offset += digit_width + 1;
+        for x in (0..width).step_by(scale as usize) {
+            ctx.img.draw_pixel(x, 0);
+            ctx.img.draw_pixel(x, height - scale);
+        }
+
+        for y in (0..height).step_by(scale as usize) {
+            ctx.img.draw_pixel(0, y);
+            ctx.img.draw_pixel(width - scale, y);
+        }
 
-        if d1 > 0 {
-            let digit = spriten("digit", d1 as _);
-            self.draw_sprite(ctx, digit, x + offset, y);
-            offset += digit_width + 1;
+        for (i, c) in digits.chars().enumerate() {
+            let n = c.to_digit(10).unwrap_or_default() as usize;
+            let x = 2 * scale + i as i64 * (digit_width + scale);
+            let digit = ctx.spriten("digit", n)?;
+            self.draw_sprite(ctx, &digit, x, 2 * scale);
         }
 
-        let digit = spriten("digit", d2 as _);
-        self.draw_sprite(ctx, digit, x + offset, y);
+        self.metrics.object_counter("staleness_marker").inc();
+        Ok(())
     }
 
     fn draw_sprite(&self, ctx: &mut RenderContext, sprite: &Sprite, x: i64, y: i64) {
         sprite.overlay(&mut ctx.img, x, y);
         self.metrics.object_counter(sprite.name()).inc();
     }
+
+    // Draws the black parts of `sprite` in the accent color of the display.
+    fn draw_accent_sprite(&self, ctx: &mut RenderContext, sprite: &Sprite, x: i64, y: i64) {
+        let color = ctx.img.accent();
+        self.draw_sprite_with(ctx, sprite, x, y, color);
+    }
+
+    // Draws the black parts of `sprite` in `color`.
+    fn draw_sprite_with(
+        &self,
+        ctx: &mut RenderContext,
+        sprite: &Sprite,
+        x: i64,
+        y: i64,
+        color: Rgba<u8>,
+    ) {
+        sprite.overlay_with(&mut ctx.img, x, y, color);
+        self.metrics.object_counter(sprite.name()).inc();
+    }
+}
+
+// Returns the times between `start` and `end` at which the local time in `tz` is `hour` o'clock.
+//
+// The times are calculated for each calendar day separately, so that days with DST transitions
+// don't shift the following days.
+fn local_times(start: Timestamp, end: Timestamp, hour: i8, tz: &TimeZone) -> Vec<Timestamp> {
+    let mut times = Vec::new();
+    let mut date = start.to_zoned(tz.clone()).date();
+
+    loop {
+        let Ok(zoned) = date.at(hour, 0, 0, 0).to_zoned(tz.clone()) else {
+            break;
+        };
+
+        let ts = zoned.timestamp();
+
+        if ts >= end {
+            break;
+        }
+
+        if ts >= start {
+            times.push(ts);
+        }
+
+        let Ok(tomorrow) = date.tomorrow() else {
+            break;
+        };
+
+        date = tomorrow;
+    }
+
+    times
 }
 
 #[derive(Debug)]
 struct RenderContext {
     img: Image,
     sun: Sun,
+    moon: Moon,
     rng: StdRng,
+    // Factor by which sprites and all other pixel sizes are multiplied.
+    scale: i64,
+    // Sprites scaled for the image, keyed by name. Keeps the sprites the same while rendering,
+    // even if they are reloaded meanwhile.
+    sprites: HashMap<String, Arc<Sprite>>,
+    // X-offset for the house, leaving room for the past hours on the left.
+    house_x: i64,
+    // The number of past hours shown left of the house.
+    history_hours: i64,
     // X-offset for the weather graph.
     x_offset: i64,
     // X-step for a single forecast.
-    x_step: i64,
+    x_step: f64,
+    // The number of hours of forecast data.
+    forecast_hours: i64,
     // Y-offset for the weather graph.
     y_offset: i64,
     // Height of the clouds.
@@ -509,28 +918,35 @@ struct RenderContext {
     max_temperature: f64,
     // Controls how many pixels to render per degree celsius.
     degrees_per_pixel: f64,
-    // The instant at which the render context was created.
+    // The instant at which the scene is rendered.
     instant: Timestamp,
     // The points for drawing the temperature graph.
     temperature_graph: BTreeMap<i64, i64>,
 }
 
 impl RenderContext {
-    fn new(data: &WeatherData, rng: StdRng) -> Self {
-        // We'll flip width and height here. The e-paper display works in portrait mode but we'd like
-        // to draw the image in landscape mode, because it's more intiutive. The rendered image gets
-        // rotated by 90 degrees before serving it to the esp32.
-        let img = Image::new(HEIGHT, WIDTH);
+    fn new(
+        data: &WeatherData,
+        instant: Timestamp,
+        img: Image,
+        rng: StdRng,
+        history_hours: i64,
+    ) -> Result<Self> {
         let (width, height) = img.dimensions();
-        let x_offset = sprite("house_00").width() as i64;
-        let x_step = (width as i64 - x_offset) / (data.forecasts.len() as i64 - 1);
+        let scale = img.scale() as i64;
+        let house_width = sprite("house_00")?.width() as i64 * scale;
+        // The past hours use the same scale as the forecast.
+        let hours = data.forecasts.len() as i64 + history_hours;
+        let x_step = (width as i64 - house_width) as f64 / hours as f64;
+        let house_x = (x_step * history_hours as f64).round() as i64;
+        let x_offset = house_x + house_width;
         let y_step = (height as f64 * 0.39).round() as i64;
         let y_offset = (height as i64 / 2) + y_step;
-        let cloud_height = sprite("cloud_02").height() as i64;
-        let instant = Timestamp::now();
+        let cloud_height = sprite("cloud_02")?.height() as i64 * scale;
 
         let coords = &data.coords;
         let sun = Sun::new(coords.latitude, coords.longitude, Some(coords.altitude));
+        let moon = Moon::new(coords.latitude, coords.longitude);
 
         let temperatures: Vec<f64> = data
             .forecasts
@@ -553,7 +969,7 @@ impl RenderContext {
         let temperature_range = max_temperature - min_temperature;
 
         let degrees_per_pixel = if temperature_range < y_step as f64 {
-            0.5
+            0.5 / scale as f64
         } else {
             temperature_range / y_step as f64
         };
@@ -561,8 +977,14 @@ impl RenderContext {
         let mut ctx = RenderContext {
             img,
             sun,
+            moon,
             rng,
+            scale,
+            sprites: HashMap::new(),
+            house_x,
+            history_hours,
             x_step,
+            forecast_hours: data.forecasts.len() as i64,
             x_offset,
             y_offset,
             cloud_height,
@@ -574,14 +996,88 @@ impl RenderContext {
         };
 
         ctx.populate_temperature_graph(data);
-        ctx
+        Ok(ctx)
+    }
+
+    // Returns the sprite called `name`, scaled for the image.
+    fn sprite(&mut self, name: &str) -> Result<Arc<Sprite>> {
+        if let Some(sprite) = self.sprites.get(name) {
+            return Ok(sprite.clone());
+        }
+
+        let sprite = scaled_sprite(name, self.scale as u32)?;
+        self.sprites.insert(name.to_owned(), sprite.clone());
+        Ok(sprite)
+    }
+
+    // Returns the sprite called `prefix_n`, where `n` has two digits, e.g. `cloud_02`.
+    fn spriten(&mut self, prefix: &str, n: usize) -> Result<Arc<Sprite>> {
+        self.sprite(&format!("{prefix}_{n:02}"))
     }
 
     fn timestamp_to_x(&self, timestamp: Timestamp) -> i64 {
-        const SECONDS_DAY: f64 = 24.0 * 60.0 * 60.0;
-        let delta = timestamp.duration_since(self.instant).as_secs_f64();
-        let width = self.img.width() as f64 - self.x_offset as f64;
-        ((delta / SECONDS_DAY) * width).round() as i64 + self.x_offset
+        const SECONDS_HOUR: f64 = 60.0 * 60.0;
+        let hours = timestamp.duration_since(self.instant).as_secs_f64() / SECONDS_HOUR;
+        (hours * self.x_step).round() as i64 + self.x_offset
+    }
+
+    // Returns the end of the forecast horizon, i.e. the time at the right edge of the image.
+    fn horizon_end(&self) -> Timestamp {
+        self.instant
+            .checked_add(SignedDuration::from_hours(self.forecast_hours))
+            .expect("timestamp overflow")
+    }
+
+    // Returns `true` if `x` is in the second half of the forecast, where the weather is further
+    // away.
+    fn is_distant(&self, x: i64) -> bool {
+        let width = self.img.width() as i64 - self.x_offset;
+        x - self.x_offset > width / 2
+    }
+
+    // Like `timestamp_to_x`, but for past timestamps which are shown left of the house.
+    fn history_x(&self, timestamp: Timestamp) -> i64 {
+        self.timestamp_to_x(timestamp) - (self.x_offset - self.house_x)
+    }
+
+    // Returns the start of the past hours shown left of the house.
+    fn history_start(&self) -> Timestamp {
+        self.instant
+            .checked_sub(SignedDuration::from_hours(self.history_hours))
+            .expect("timestamp overflow")
+    }
+
+    // Returns the times of all occurrences of the sun phase within the forecast horizon. There
+    // may be none during polar day or night.
+    fn sun_phases(&self, phase: SunPhase) -> Vec<Timestamp> {
+        let end = self.horizon_end();
+        let mut phases = Vec::new();
+        let mut ts = self.instant;
+
+        while let Some(next) = self.sun.next_phase(ts, phase).filter(|&next| next < end) {
+            phases.push(next);
+            ts = next;
+        }
+
+        phases
+    }
+
+    // Returns the times of all moonrises within the forecast horizon.
+    fn moonrises(&self) -> Vec<Timestamp> {
+        // The moon rises about every 25 hours. Searching again right after a moonrise may find
+        // the same one again due to the interpolation, so skip ahead a bit.
+        const SKIP: SignedDuration = SignedDuration::from_hours(6);
+
+        let end = self.horizon_end();
+        let mut moonrises = Vec::new();
+        let mut ts = self.instant;
+
+        while let Some(moonrise) = self.moon.next_rise(ts).filter(|&moonrise| moonrise < end) {
+            moonrises.push(moonrise);
+            ts = moonrise.checked_add(SKIP).expect("timestamp overflow");
+        }
+
+        moonrises
     }
 
     fn temperature_to_y(&self, temperature: f64) -> i64 {
@@ -590,7 +1086,7 @@ impl RenderContext {
     }
 
     fn forecast_x(&self, i: usize) -> i64 {
-        self.x_offset + (self.x_step * (i as i64 + 1))
+        self.x_offset + (self.x_step * (i as f64 + 1.0)).round() as i64
     }
 
     fn forecast_coords(&self, i: usize, data_point: &DataPoint) -> (i64, i64) {
@@ -612,7 +1108,13 @@ impl RenderContext {
         // Collect points for the current temperature below the house.
         let y = self.temperature_to_y(data.current.air_temperature);
 
-        collect_points(&mut self.temperature_graph, 0, y, self.x_offset - 1, y);
+        collect_points(
+            &mut self.temperature_graph,
+            self.house_x,
+            y,
+            self.x_offset - 1,
+            y,
+        );
 
         // Collect points between the current temperature and the first forecasts.
         let (x1, y1) = (self.x_offset - 1, y);
@@ -629,3 +1131,464 @@ impl RenderContext {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{config::Location, weather::Scenario};
+    use actix_web::{ResponseError, http::StatusCode};
+    use prometheus::Registry;
+    use rand::SeedableRng;
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn location() -> Location {
+        Location {
+            latitude: 52.52,
+            longitude: 13.405,
+            altitude: None,
+            timezone: None,
+        }
+    }
+
+    // Generates 24 hours of weather data for `scenario`, along with the rng to render it with.
+    fn scenario_data(scenario: Scenario, instant: Timestamp) -> (WeatherData, StdRng) {
+        let mut rng = StdRng::seed_from_u64(1);
+        let data = scenario
+            .generate(&location(), instant, 24, &mut rng)
+            .unwrap();
+        (data, rng)
+    }
+
+    fn renderer() -> Renderer {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "latitude": 52.52,
+            "longitude": 13.405,
+        }))
+        .unwrap();
+        let metrics = Metrics::new("test", &Registry::new()).unwrap();
+        let time_zone = TimeZone::get("Europe/Berlin").unwrap();
+
+        Renderer::new(&config, time_zone, metrics)
+    }
+
+    fn render(instant: Timestamp, seed: u64) -> Image {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let data = Scenario::ClearingFog
+            .generate(&location(), instant, 24, &mut rng)
+            .unwrap();
+
+        renderer()
+            .render(&data, instant, None, None, false, rng)
+            .unwrap()
+    }
+
+    #[test]
+    fn deterministic() {
+        let instant = ts("2025-06-14T12:30:00Z");
+
+        assert_eq!(*render(instant, 1), *render(instant, 1));
+        assert_ne!(*render(instant, 1), *render(instant, 2));
+    }
+
+    #[test]
+    fn displays() {
+        let instant = ts("2025-06-14T12:30:00Z");
+        let (data, rng) = scenario_data(Scenario::Gale, instant);
+
+        for (display, size) in [
+            (Display::Epd2in9V2, (296, 128)),
+            (Display::Epd4in2, (400, 300)),
+            (Display::Epd7in5V2, (800, 480)),
+        ] {
+            let img = renderer()
+                .render(&data, instant, Some(display), None, false, rng.clone())
+                .unwrap();
+            assert_eq!(img.dimensions(), size);
+
+            let (width, height) = display.native_size();
+            let (epd, _) = img.encode(ImageFormat::Epd).unwrap();
+            assert_eq!(epd.len(), (width as usize).div_ceil(8) * height as usize);
+        }
+    }
+
+    #[test]
+    fn tri_color() {
+        let instant = ts("2025-06-14T12:30:00Z");
+        let (data, rng) = scenario_data(Scenario::ThunderstormFront, instant);
+        let accent_pixels = |img: &Image| img.pixels().filter(|&&p| p == img::ACCENT).count();
+
+        let img = renderer()
+            .render(
+                &data,
+                instant,
+                Some(Display::Epd2in9Bc),
+                None,
+                false,
+                rng.clone(),
+            )
+            .unwrap();
+        assert!(accent_pixels(&img) > 0);
+
+        // Both bit-planes are sent to the display.
+        let (width, height) = Display::Epd2in9Bc.native_size();
+        let (epd, _) = img.encode(ImageFormat::Epd).unwrap();
+        assert_eq!(
+            epd.len(),
+            2 * (width as usize).div_ceil(8) * height as usize
+        );
+
+        // Black and white displays draw accents in black.
+        let img = renderer()
+            .render(&data, instant, Some(Display::Epd2in9V2), None, false, rng)
+            .unwrap();
+        assert_eq!(accent_pixels(&img), 0);
+    }
+
+    #[test]
+    fn grayscale() {
+        let instant = ts("2025-06-14T06:30:00Z");
+        let (data, rng) = scenario_data(Scenario::ClearingFog, instant);
+        let gray_pixels = |img: &Image| {
+            img.pixels()
+                .filter(|&&p| p == img::LIGHT_GRAY || p == img::DARK_GRAY)
+                .count()
+        };
+
+        let img = renderer()
+            .render(&data, instant, None, None, true, rng.clone())
+            .unwrap();
+        assert!(gray_pixels(&img) > 0);
+
+        // Two bits per pixel.
+        let (width, height) = Display::Epd2in9V2.native_size();
+        let (epd, _) = img.encode(ImageFormat::EpdGray).unwrap();
+        assert_eq!(epd.len(), (width * height / 4) as usize);
+
+        let img = renderer()
+            .render(&data, instant, None, None, false, rng.clone())
+            .unwrap();
+        assert_eq!(gray_pixels(&img), 0);
+
+        // One bit per pixel can't show gray tones.
+        let img = renderer()
+            .render(&data, instant, None, None, true, rng.clone())
+            .unwrap();
+        let err = img.encode(ImageFormat::Epd).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        // Only some controllers support grayscale.
+        let img = renderer()
+            .render(&data, instant, Some(Display::Epd4in2), None, true, rng)
+            .unwrap();
+        let err = img.encode(ImageFormat::EpdGray).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn narrow_hours() {
+        // History and forecast share the width of the smallest display, leaving few pixels for
+        // each hour.
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "latitude": 52.52,
+            "longitude": 13.405,
+            "forecast_hours": 6,
+            "history": { "enabled": true, "hours": 24 },
+        }))
+        .unwrap();
+        let metrics = Metrics::new("test", &Registry::new()).unwrap();
+        let time_zone = TimeZone::get("Europe/Berlin").unwrap();
+        let renderer = Renderer::new(&config, time_zone, metrics);
+
+        let instant = ts("2025-06-14T04:30:00Z");
+        let mut rng = StdRng::seed_from_u64(1);
+        let data = Scenario::ClearingFog
+            .generate(&location(), instant, 6, &mut rng)
+            .unwrap();
+        assert!(data.forecasts[0].fog_area_fraction > 0.0);
+
+        let img = renderer
+            .render(&data, instant, Some(Display::Epd2in13Bc), None, false, rng)
+            .unwrap();
+        assert_eq!(img.dimensions(), (212, 104));
+    }
+
+    #[test]
+    fn twilight_fog() {
+        // Between sunset and dusk, so the scene is shaded.
+        let instant = ts("2025-06-14T19:50:00Z");
+        let (data, rng) = scenario_data(Scenario::ClearingFog, instant);
+        let img = Image::new(Display::default(), Layout::default(), true, None);
+        let mut ctx = RenderContext::new(&data, instant, img, rng, 0).unwrap();
+        assert!(ctx.sun.is_twilight(instant) && !ctx.sun.is_dark(instant));
+
+        let mut forecast = data.forecasts[0].clone();
+        forecast.fog_area_fraction = 100.0;
+        let x = ctx.forecast_x(0);
+        let width = (ctx.x_step * 4.0).round() as i64;
+        renderer().draw_fog(&mut ctx, &forecast, x, 0, width);
+
+        let fog: Vec<_> = ctx
+            .img
+            .enumerate_pixels()
+            .filter(|&(_, _, &p)| p != WHITE)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert!(!fog.is_empty());
+
+        // The fog is drawn darker than the shaded background.
+        ctx.img.shade();
+        assert_eq!(*ctx.img.get_pixel(0, 0), img::LIGHT_GRAY);
+
+        for (x, y) in fog {
+            assert_eq!(*ctx.img.get_pixel(x, y), img::DARK_GRAY);
+        }
+    }
+
+    #[test]
+    fn portrait() {
+        let instant = ts("2025-06-14T12:30:00Z");
+        let (data, rng) = scenario_data(Scenario::Gale, instant);
+
+        for (display, size) in [
+            (Display::Epd2in9V2, (128, 296)),
+            (Display::Epd7in5V2, (480, 800)),
+        ] {
+            let layout = Some(Layout::Portrait);
+            let img = renderer()
+                .render(&data, instant, Some(display), layout, false, rng.clone())
+                .unwrap();
+            assert_eq!(img.dimensions(), size);
+
+            // The frame buffer is the same in both layouts.
+            let (width, height) = display.native_size();
+            let (epd, _) = img.encode(ImageFormat::Epd).unwrap();
+            assert_eq!(epd.len(), (width as usize).div_ceil(8) * height as usize);
+        }
+    }
+
+    #[test]
+    fn scale() {
+        // Sprites are scaled up as far as the scene fits the display.
+        for (display, layout, scale) in [
+            (Display::Epd2in9V2, Layout::Landscape, 1),
+            (Display::Epd2in13Bc, Layout::Landscape, 1),
+            (Display::Epd4in2, Layout::Landscape, 2),
+            (Display::Epd7in5V2, Layout::Landscape, 3),
+            (Display::Epd7in5V2, Layout::Portrait, 3),
+        ] {
+            assert_eq!(Image::new(display, layout, false, None).scale(), scale);
+        }
+
+        let img = Image::new(Display::Epd7in5V2, Layout::Landscape, false, Some(2));
+        assert_eq!(img.scale(), 2);
+    }
+
+    #[test]
+    fn scaled_scene() {
+        let instant = ts("2025-06-14T10:30:00Z");
+        // No fog or precipitation, whose number of random draws depends on the scale.
+        let (data, rng) = scenario_data(Scenario::Heatwave, instant);
+
+        let (width, height) = Display::Epd2in9V2.canvas_size();
+        let draw = |img| {
+            renderer()
+                .draw_scene(&data, instant, img, rng.clone())
+                .unwrap()
+        };
+        let small = draw(Image::with_size(width, height, 1));
+        let large = draw(Image::with_size(width * 2, height * 2, 2));
+
+        // Positions are rounded in the scaled scene, so they may be off by one pixel of it.
+        let drawn_near = |img: &Image, x: u32, y: u32, radius: u32| {
+            let xs = x.saturating_sub(radius)..=(x + radius).min(img.width() - 1);
+            let ys = y.saturating_sub(radius)..=(y + radius).min(img.height() - 1);
+            xs.flat_map(|x| ys.clone().map(move |y| (x, y)))
+                .any(|(x, y)| *img.get_pixel(x, y) != WHITE)
+        };
+
+        let drawn: Vec<_> = small
+            .enumerate_pixels()
+            .filter(|&(_, _, &p)| p != WHITE)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let matching = drawn
+            .iter()
+            .filter(|&&(x, y)| drawn_near(&large, 2 * x, 2 * y, 2))
+            .count();
+        assert!(!drawn.is_empty());
+        assert!(
+            matching * 100 >= drawn.len() * 95,
+            "{matching} of {}",
+            drawn.len()
+        );
+
+        // Every second pixel of the large scene is drawn in the small one as well.
+        let sampled: Vec<_> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| *large.get_pixel(2 * x, 2 * y) != WHITE)
+            .collect();
+        let matching = sampled
+            .iter()
+            .filter(|&&(x, y)| drawn_near(&small, x, y, 1))
+            .count();
+        assert!(
+            matching * 100 >= sampled.len() * 95,
+            "{matching} of {}",
+            sampled.len()
+        );
+    }
+
+    #[test]
+    fn three_digit_number() {
+        let instant = ts("2025-06-14T12:30:00Z");
+        let (data, rng) = scenario_data(Scenario::Gale, instant);
+        let img = Image::new(Display::default(), Layout::default(), false, None);
+        let mut ctx = RenderContext::new(&data, instant, img, rng, 0).unwrap();
+
+        // 104 in degrees Fahrenheit is centered around the digits, the sign is left of them.
+        let (x, y) = (100, 50);
+        let end = renderer().draw_number(&mut ctx, x, y, 104, false).unwrap();
+
+        let digit_width = sprite("digit_00").unwrap().width() as i64;
+        let start = x - 3 * (digit_width + 1) / 2 - digit_width;
+        assert_eq!(end, start + 4 * (digit_width + 1) - 1);
+
+        let mut expected = Image::new(Display::default(), Layout::default(), false, None);
+
+        for (i, name) in ["digit_10", "digit_01", "digit_00", "digit_04"]
+            .into_iter()
+            .enumerate()
+        {
+            let x = start + i as i64 * (digit_width + 1);
+            sprite(name).unwrap().overlay(&mut expected, x, y);
+        }
+
+        assert_eq!(*ctx.img, *expected);
+    }
+
+    #[test]
+    fn staleness() {
+        let fetched_at = ts("2025-06-14T09:30:00Z");
+        let (mut data, rng) = scenario_data(Scenario::Gale, fetched_at);
+        data.stale = true;
+
+        let digit = sprite("digit_00").unwrap();
+        let (digit_width, digit_height) = (digit.width(), digit.height());
+
+        // The age in hours is drawn into a frame in the top left corner.
+        for (hours, digits) in [(3, 1), (10, 2)] {
+            let instant = fetched_at
+                .checked_add(SignedDuration::from_mins(hours * 60 - 10))
+                .unwrap();
+            let img = renderer()
+                .render(&data, instant, None, None, false, rng.clone())
+                .unwrap();
+
+            let width = digits * (digit_width + 1) + 3;
+            let height = digit_height + 4;
+
+            for x in 0..width {
+                assert_eq!(*img.get_pixel(x, 0), BLACK);
+                assert_eq!(*img.get_pixel(x, height - 1), BLACK);
+            }
+
+            for y in 0..height {
+                assert_eq!(*img.get_pixel(0, y), BLACK);
+                assert_eq!(*img.get_pixel(width - 1, y), BLACK);
+            }
+        }
+
+        // Stale data starts at the hour of the instant it's rendered for.
+        let instant = ts("2025-06-14T14:30:00Z");
+        let mut trimmed = data.clone();
+        trimmed.trim_to(instant);
+        assert_eq!(trimmed.current.timestamp, ts("2025-06-14T14:00:00Z"));
+
+        let stale = renderer()
+            .render(&data, instant, None, None, false, rng.clone())
+            .unwrap();
+        let expected = renderer()
+            .render(&trimmed, instant, None, None, false, rng)
+            .unwrap();
+        assert_eq!(*stale, *expected);
+    }
+
+    #[test]
+    fn moonrises() {
+        let instant = ts("2024-10-17T11:26:00Z");
+        let mut rng = StdRng::seed_from_u64(1);
+        let data = Scenario::Gale
+            .generate(&location(), instant, 48, &mut rng)
+            .unwrap();
+        let img = Image::new(Display::default(), Layout::default(), false, None);
+        let ctx = RenderContext::new(&data, instant, img, rng, 0).unwrap();
+
+        // Each moonrise within the two days is found exactly once.
+        let moonrises = ctx.moonrises();
+        assert_eq!(moonrises.len(), 2);
+        assert!(moonrises[1].duration_since(moonrises[0]) > SignedDuration::from_hours(24));
+    }
+
+    #[test]
+    fn night_mode() {
+        let black_pixels = |img: &Image| img.pixels().filter(|&&p| p == BLACK).count();
+
+        let day = render(ts("2025-06-14T10:00:00Z"), 1);
+        let night = render(ts("2025-06-14T23:00:00Z"), 1);
+        let pixels = (day.width() * day.height()) as usize;
+
+        // The colors are inverted at night.
+        assert!(black_pixels(&day) < pixels / 2);
+        assert!(black_pixels(&night) > pixels / 2);
+    }
+
+    #[test]
+    fn local_midnight_and_midday() {
+        let tz = TimeZone::get("Europe/Oslo").unwrap();
+        let start = ts("2025-06-14T12:30:00Z");
+        let end = ts("2025-06-16T12:30:00Z");
+
+        assert_eq!(
+            local_times(start, end, 0, &tz),
+            [ts("2025-06-14T22:00:00Z"), ts("2025-06-15T22:00:00Z")]
+        );
+        assert_eq!(
+            local_times(start, end, 12, &tz),
+            [ts("2025-06-15T10:00:00Z"), ts("2025-06-16T10:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn local_times_across_dst_transitions() {
+        let tz = TimeZone::get("Europe/Berlin").unwrap();
+
+        // Clocks go forward on 2025-03-30 at 02:00.
+        let start = ts("2025-03-29T12:00:00Z");
+        let end = ts("2025-03-31T12:00:00Z");
+
+        assert_eq!(
+            local_times(start, end, 0, &tz),
+            [ts("2025-03-29T23:00:00Z"), ts("2025-03-30T22:00:00Z")]
+        );
+        assert_eq!(
+            local_times(start, end, 12, &tz),
+            [ts("2025-03-30T10:00:00Z"), ts("2025-03-31T10:00:00Z")]
+        );
+
+        // Clocks go back on 2025-10-26 at 03:00.
+        let start = ts("2025-10-25T12:00:00Z");
+        let end = ts("2025-10-27T12:00:00Z");
+
+        assert_eq!(
+            local_times(start, end, 0, &tz),
+            [ts("2025-10-25T22:00:00Z"), ts("2025-10-26T23:00:00Z")]
+        );
+        assert_eq!(
+            local_times(start, end, 12, &tz),
+            [ts("2025-10-26T11:00:00Z"), ts("2025-10-27T11:00:00Z")]
+        );
+    }
+}
@@ -1,111 +1,285 @@
 use super::{BLACK, TRANSPARENT, WHITE};
-use crate::error::Result;
-use image::{RgbaImage, imageops};
+use crate::error::{Error, Result};
+use image::{Rgba, RgbaImage, imageops};
 use std::collections::HashMap;
 use std::ops::Deref;
-use std::sync::OnceLock;
-use tracing::trace;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, trace, warn};
 
-macro_rules! load_sprite {
-    ($map:ident, $name:literal) => {
-        $map.insert(
+// How often the sprite directory is checked for changes in watch mode.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+macro_rules! embed_sprite {
+    ($name:literal) => {
+        (
             $name,
-            Sprite::from_slice(
-                $name,
-                include_bytes!(concat!("data/sprites/", $name, ".png")),
-            )
-            .unwrap(),
-        );
+            include_bytes!(concat!("data/sprites/", $name, ".png")).as_slice(),
+        )
     };
 }
 
-pub(super) fn sprites() -> &'static HashMap<&'static str, Sprite> {
-    static SPRITES: OnceLock<HashMap<&str, Sprite>> = OnceLock::new();
-
-    SPRITES.get_or_init(|| {
-        let mut m = HashMap::new();
-        // Cloud
-        load_sprite!(m, "cloud_02");
-        load_sprite!(m, "cloud_03");
-        load_sprite!(m, "cloud_05");
-        load_sprite!(m, "cloud_10");
-        load_sprite!(m, "cloud_30");
-        load_sprite!(m, "cloud_50");
-        // Digit
-        load_sprite!(m, "digit_00");
-        load_sprite!(m, "digit_01");
-        load_sprite!(m, "digit_02");
-        load_sprite!(m, "digit_03");
-        load_sprite!(m, "digit_04");
-        load_sprite!(m, "digit_05");
-        load_sprite!(m, "digit_06");
-        load_sprite!(m, "digit_07");
-        load_sprite!(m, "digit_08");
-        load_sprite!(m, "digit_09");
-        load_sprite!(m, "digit_10");
-        load_sprite!(m, "digit_11");
-        load_sprite!(m, "digit_12");
-        // East
-        load_sprite!(m, "east_00");
-        load_sprite!(m, "east_01");
-        load_sprite!(m, "east_02");
-        load_sprite!(m, "east_03");
-        // Flower
-        load_sprite!(m, "flower_00");
-        load_sprite!(m, "flower_01");
-        // House
-        load_sprite!(m, "house_00");
-        load_sprite!(m, "house_01");
-        load_sprite!(m, "house_02");
-        // Moon
-        load_sprite!(m, "moon_00");
-        load_sprite!(m, "moon_01");
-        // Palm
-        load_sprite!(m, "palm_00");
-        load_sprite!(m, "palm_01");
-        load_sprite!(m, "palm_02");
-        load_sprite!(m, "palm_03");
-        // Pine
-        load_sprite!(m, "pine_00");
-        load_sprite!(m, "pine_01");
-        load_sprite!(m, "pine_02");
-        load_sprite!(m, "pine_03");
-        // Sun
-        load_sprite!(m, "sun_00");
-        // Temp
-        load_sprite!(m, "temp_00");
-        // Tree
-        load_sprite!(m, "tree_00");
-        load_sprite!(m, "tree_01");
-        load_sprite!(m, "tree_02");
-        load_sprite!(m, "tree_03");
-        // Lightning
-        load_sprite!(m, "lightning_00");
-        load_sprite!(m, "lightning_01");
-        load_sprite!(m, "lightning_02");
-        load_sprite!(m, "lightning_03");
-        load_sprite!(m, "lightning_04");
-        m
-    })
-}
-
-pub(super) fn sprite(name: &str) -> &'static Sprite {
-    sprites().get(name).unwrap()
-}
-
-pub(super) fn spriten(prefix: &str, n: usize) -> &'static Sprite {
-    let name = format!("{prefix}_{n:02}");
-    sprite(&name)
+// The sprites compiled into the binary. These define the set of valid sprite names and sizes.
+const EMBEDDED: &[(&str, &[u8])] = &[
+    // Cloud
+    embed_sprite!("cloud_02"),
+    embed_sprite!("cloud_03"),
+    embed_sprite!("cloud_05"),
+    embed_sprite!("cloud_10"),
+    embed_sprite!("cloud_30"),
+    embed_sprite!("cloud_50"),
+    // Digit
+    embed_sprite!("digit_00"),
+    embed_sprite!("digit_01"),
+    embed_sprite!("digit_02"),
+    embed_sprite!("digit_03"),
+    embed_sprite!("digit_04"),
+    embed_sprite!("digit_05"),
+    embed_sprite!("digit_06"),
+    embed_sprite!("digit_07"),
+    embed_sprite!("digit_08"),
+    embed_sprite!("digit_09"),
+    embed_sprite!("digit_10"),
+    embed_sprite!("digit_11"),
+    embed_sprite!("digit_12"),
+    // East
+    embed_sprite!("east_00"),
+    embed_sprite!("east_01"),
+    embed_sprite!("east_02"),
+    embed_sprite!("east_03"),
+    // Flower
+    embed_sprite!("flower_00"),
+    embed_sprite!("flower_01"),
+    // House
+    embed_sprite!("house_00"),
+    embed_sprite!("house_01"),
+    embed_sprite!("house_02"),
+    // Moon
+    embed_sprite!("moon_00"),
+    embed_sprite!("moon_01"),
+    // Palm
+    embed_sprite!("palm_00"),
+    embed_sprite!("palm_01"),
+    embed_sprite!("palm_02"),
+    embed_sprite!("palm_03"),
+    // Pine
+    embed_sprite!("pine_00"),
+    embed_sprite!("pine_01"),
+    embed_sprite!("pine_02"),
+    embed_sprite!("pine_03"),
+    // Sun
+    embed_sprite!("sun_00"),
+    // Temp
+    embed_sprite!("temp_00"),
+    // Tree
+    embed_sprite!("tree_00"),
+    embed_sprite!("tree_01"),
+    embed_sprite!("tree_02"),
+    embed_sprite!("tree_03"),
+    // Lightning
+    embed_sprite!("lightning_00"),
+    embed_sprite!("lightning_01"),
+    embed_sprite!("lightning_02"),
+    embed_sprite!("lightning_03"),
+    embed_sprite!("lightning_04"),
+    // Warning
+    embed_sprite!("warning_00"),
+    embed_sprite!("warning_01"),
+    embed_sprite!("warning_02"),
+    // Unit
+    embed_sprite!("unit_00"),
+    embed_sprite!("unit_01"),
+];
+
+// The sprites used for rendering. Starts out with the embedded sprites, some of which may be
+// replaced by sprites loaded from a directory.
+static SPRITES: LazyLock<RwLock<Sprites>> = LazyLock::new(|| RwLock::new(Sprites::new(embedded())));
+
+#[derive(Debug)]
+struct Sprites {
+    // The sprites in their original size, keyed by name.
+    original: HashMap<String, Arc<Sprite>>,
+    // Scaled copies of the original sprites, keyed by name and factor. Filled on first use.
+    scaled: HashMap<(String, u32), Arc<Sprite>>,
+}
+
+impl Sprites {
+    fn new(original: HashMap<String, Arc<Sprite>>) -> Self {
+        Sprites {
+            original,
+            scaled: HashMap::new(),
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<Arc<Sprite>> {
+        self.original
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::new(format!("sprite {name:?} not found")))
+    }
+}
+
+fn embedded() -> HashMap<String, Arc<Sprite>> {
+    EMBEDDED
+        .iter()
+        .map(|&(name, buf)| {
+            let sprite = Sprite::from_slice(name, buf).unwrap();
+            (name.to_owned(), Arc::new(sprite))
+        })
+        .collect()
+}
+
+/// Loads the PNG files in `dir`, replacing the embedded sprites of the same name.
+///
+/// Fails if a file does not match the name of an embedded sprite, e.g. `cloud_02.png`, or if its
+/// size differs from the embedded sprite. The layout depends on the sprite sizes, so they can't
+/// change. Nothing is replaced in that case.
+pub fn load_sprites(dir: &Path) -> Result<()> {
+    load_into(&SPRITES, dir)
+}
+
+/// Reloads the sprites from `dir` whenever a file in there changes. Invalid sprites are logged
+/// and the previous sprites are kept.
+pub async fn watch_sprites(dir: PathBuf) {
+    watch_into(&SPRITES, &dir).await
+}
+
+fn load_into(sprites: &RwLock<Sprites>, dir: &Path) -> Result<()> {
+    let original = read_sprites(dir)?;
+    *sprites.write().unwrap() = Sprites::new(original);
+    Ok(())
+}
+
+async fn watch_into(sprites: &RwLock<Sprites>, dir: &Path) {
+    let mut last_modified = modified_times(dir).ok();
+
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+
+        let modified = match modified_times(dir) {
+            Ok(modified) => modified,
+            Err(err) => {
+                warn!("failed to check sprite directory {}: {err}", dir.display());
+                continue;
+            }
+        };
+
+        if last_modified.as_ref() == Some(&modified) {
+            continue;
+        }
+
+        last_modified = Some(modified);
+
+        match load_into(sprites, dir) {
+            Ok(()) => info!("reloaded sprites from {}", dir.display()),
+            Err(err) => warn!("failed to reload sprites: {err}"),
+        }
+    }
+}
+
+// Returns the embedded sprites with the PNG files in `dir` replacing those of the same name.
+fn read_sprites(dir: &Path) -> Result<HashMap<String, Arc<Sprite>>> {
+    let mut sprites = embedded();
+
+    for path in png_files(dir)? {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+
+        let Some(embedded) = sprites.get(name) else {
+            return Err(Error::new(format!(
+                "unknown sprite {name:?} in {}",
+                path.display()
+            )));
+        };
+
+        let sprite = Sprite::from_slice(name, &std::fs::read(&path)?)?;
+
+        if sprite.dimensions() != embedded.dimensions() {
+            return Err(Error::new(format!(
+                "sprite {name:?} in {} must be {:?} pixels, got {:?}",
+                path.display(),
+                embedded.dimensions(),
+                sprite.dimensions()
+            )));
+        }
+
+        debug!(name, path = %path.display(), "overriding sprite");
+        sprites.insert(name.to_owned(), Arc::new(sprite));
+    }
+
+    Ok(sprites)
+}
+
+fn png_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+
+        if path.extension().is_some_and(|ext| ext == "png") {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+fn modified_times(dir: &Path) -> Result<Vec<(PathBuf, SystemTime)>> {
+    png_files(dir)?
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path)?.modified()?;
+            Ok((path, modified))
+        })
+        .collect()
+}
+
+/// Returns the sprite called `name`.
+pub(super) fn sprite(name: &str) -> Result<Arc<Sprite>> {
+    SPRITES.read().unwrap().get(name)
+}
+
+/// Returns the sprite called `name`, `factor` times as large. Scaled sprites are kept until the
+/// sprites are reloaded, so that each one is only scaled once.
+pub(super) fn scaled_sprite(name: &str, factor: u32) -> Result<Arc<Sprite>> {
+    scaled_from(&SPRITES, name, factor)
+}
+
+fn scaled_from(sprites: &RwLock<Sprites>, name: &str, factor: u32) -> Result<Arc<Sprite>> {
+    if factor == 1 {
+        return sprites.read().unwrap().get(name);
+    }
+
+    let key = (name.to_owned(), factor);
+
+    if let Some(sprite) = sprites.read().unwrap().scaled.get(&key) {
+        return Ok(sprite.clone());
+    }
+
+    // Scale the sprite under the write lock, so that a concurrent reload can't leave a scaled
+    // copy of a replaced sprite behind.
+    let mut sprites = sprites.write().unwrap();
+    let original = sprites.get(name)?;
+    let sprite = sprites
+        .scaled
+        .entry(key)
+        .or_insert_with(|| Arc::new(original.scaled(factor)));
+
+    Ok(sprite.clone())
 }
 
 #[derive(Debug)]
 pub(super) struct Sprite {
-    name: &'static str,
+    name: String,
     img: RgbaImage,
 }
 
 impl Sprite {
-    fn from_slice(name: &'static str, buf: &[u8]) -> Result<Self> {
+    fn from_slice(name: &str, buf: &[u8]) -> Result<Self> {
         let mut img = image::load_from_memory(buf)?.into_rgba8();
 
         // Make any non-black, non-white pixels transparent.
@@ -115,17 +289,55 @@ impl Sprite {
             }
         }
 
-        Ok(Sprite { name, img })
+        Ok(Sprite {
+            name: name.to_owned(),
+            img,
+        })
+    }
+
+    pub(super) fn name(&self) -> &str {
+        &self.name
     }
 
-    pub(super) fn name(&self) -> &'static str {
-        self.name
+    /// Returns a copy of the sprite which is `factor` times as large, using nearest-neighbor
+    /// sampling to keep the edges sharp.
+    fn scaled(&self, factor: u32) -> Sprite {
+        let (width, height) = self.img.dimensions();
+        let img = RgbaImage::from_fn(width * factor, height * factor, |x, y| {
+            *self.img.get_pixel(x / factor, y / factor)
+        });
+
+        Sprite {
+            name: self.name.clone(),
+            img,
+        }
     }
 
     pub(super) fn overlay(&self, image: &mut RgbaImage, x: i64, y: i64) {
         trace!("placing sprite {} at ({x}, {y})", self.name);
         imageops::overlay(image, &self.img, x, y);
     }
+
+    /// Like `overlay`, but draws the black pixels of the sprite in `color`.
+    pub(super) fn overlay_with(&self, image: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+        trace!("placing sprite {} at ({x}, {y}) in {color:?}", self.name);
+
+        for (sx, sy, pixel) in self.img.enumerate_pixels() {
+            let (ix, iy) = (x + sx as i64, y + sy as i64);
+
+            if *pixel == TRANSPARENT
+                || ix < 0
+                || iy < 0
+                || ix >= image.width() as i64
+                || iy >= image.height() as i64
+            {
+                continue;
+            }
+
+            let pixel = if *pixel == BLACK { color } else { *pixel };
+            image.put_pixel(ix as u32, iy as u32, pixel);
+        }
+    }
 }
 
 impl Deref for Sprite {
@@ -135,3 +347,112 @@ impl Deref for Sprite {
         &self.img
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn embedded_bytes(name: &str) -> &'static [u8] {
+        EMBEDDED.iter().find(|(n, _)| *n == name).unwrap().1
+    }
+
+    fn sprite_dir(files: &[(&str, &[u8])]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+
+        for (file, buf) in files {
+            std::fs::write(dir.path().join(file), buf).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn missing_sprite() {
+        assert!(sprite("cloud_02").is_ok());
+
+        let err = sprite("cloud_99").unwrap_err();
+        assert_eq!(err.to_string(), r#"sprite "cloud_99" not found"#);
+    }
+
+    #[test]
+    fn scaled() {
+        let digit = sprite("digit_01").unwrap();
+        let scaled = digit.scaled(3);
+
+        assert_eq!(scaled.dimensions(), (9, 15));
+        assert_eq!(scaled.name(), "digit_01");
+
+        for (x, y, pixel) in scaled.enumerate_pixels() {
+            assert_eq!(pixel, digit.get_pixel(x / 3, y / 3));
+        }
+
+        assert_eq!(scaled_sprite("digit_01", 3).unwrap().img, scaled.img);
+        assert!(Arc::ptr_eq(
+            &scaled_sprite("digit_01", 1).unwrap(),
+            &sprite("digit_01").unwrap()
+        ));
+    }
+
+    #[test]
+    fn override_sprites() {
+        // The crescent moon looks like the full moon now.
+        let dir = sprite_dir(&[("moon_00.png", embedded_bytes("moon_01"))]);
+        let sprites = read_sprites(dir.path()).unwrap();
+        assert_eq!(sprites["moon_00"].img, sprites["moon_01"].img);
+        assert_eq!(sprites["moon_00"].name(), "moon_00");
+
+        let dir = sprite_dir(&[("moon_99.png", embedded_bytes("moon_01"))]);
+        assert!(read_sprites(dir.path()).is_err());
+
+        // Sprites must keep their size.
+        let dir = sprite_dir(&[("house_00.png", embedded_bytes("digit_00"))]);
+        assert!(read_sprites(dir.path()).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn watch() {
+        // Watch a cache of its own, so that tests rendering concurrently are not affected.
+        let sprites = RwLock::new(Sprites::new(embedded()));
+        let sprite = |name| sprites.read().unwrap().get(name).unwrap();
+        let dir = sprite_dir(&[]);
+
+        let test = async {
+            tokio::time::sleep(WATCH_INTERVAL / 2).await;
+
+            // Scaled sprites are cached.
+            let scaled = scaled_from(&sprites, "moon_00", 2).unwrap();
+            assert!(Arc::ptr_eq(
+                &scaled,
+                &scaled_from(&sprites, "moon_00", 2).unwrap()
+            ));
+
+            // The crescent moon looks like the full moon now.
+            std::fs::write(dir.path().join("moon_00.png"), embedded_bytes("moon_01")).unwrap();
+            tokio::time::sleep(WATCH_INTERVAL).await;
+
+            let reloaded = sprite("moon_00");
+            assert_eq!(reloaded.img, sprite("moon_01").img);
+
+            // The cached scaled sprites are dropped on reload.
+            let rescaled = scaled_from(&sprites, "moon_00", 2).unwrap();
+            assert_ne!(scaled.img, rescaled.img);
+            assert_eq!(rescaled.img, reloaded.scaled(2).img);
+
+            // An invalid sprite keeps the previous sprites.
+            std::fs::write(dir.path().join("house_00.png"), embedded_bytes("digit_00")).unwrap();
+            tokio::time::sleep(WATCH_INTERVAL).await;
+
+            assert!(Arc::ptr_eq(&reloaded, &sprite("moon_00")));
+            assert_ne!(
+                sprite("house_00").dimensions(),
+                sprite("digit_00").dimensions()
+            );
+        };
+
+        tokio::select! {
+            biased;
+            _ = watch_into(&sprites, dir.path()) => unreachable!(),
+            _ = test => {}
+        }
+    }
+}
@@ -2,32 +2,44 @@ mod app;
 mod config;
 mod error;
 mod graphics;
+mod moon;
 mod sun;
 mod weather;
 
 use crate::{
-    app::{AppState, Metrics},
-    config::Config,
+    app::{AppState, LocationState, Metrics},
+    config::{Config, DEFAULT_LOCATION},
     error::Result,
-    graphics::ImageFormat,
+    graphics::{Display, ImageFormat, Layout, load_sprites, watch_sprites},
+    weather::Scenario,
 };
 use actix_web::{
-    App, HttpResponse, HttpServer, get,
+    App, HttpResponse, HttpServer,
+    error::ErrorNotFound,
+    get,
     http::header::ContentType,
     middleware::Logger,
     web::{Data, Path, Query},
 };
 use actix_web_prom::PrometheusMetricsBuilder;
+use jiff::Timestamp;
 use rand::{SeedableRng, rngs::StdRng};
 use serde::Deserialize;
 
 #[derive(Deserialize, Clone, Debug)]
 struct ImageQuery {
-    /// Adds a lot of randomness to the weather data to make the weather seem unpredictable.
-    #[serde(default)]
-    wreck_havoc: bool,
+    /// Renders synthetic weather data for a named scenario instead of the actual weather.
+    scenario: Option<Scenario>,
     /// A seed for the RNG to produce stable randomness.
     seed: Option<u64>,
+    /// Renders the scene for this instant instead of now.
+    at: Option<Timestamp>,
+    /// Renders the image for this display instead of the configured one.
+    display: Option<Display>,
+    /// Arranges the scene in this layout instead of the configured one.
+    layout: Option<Layout>,
+    /// Renders gray tones. Defaults to `true` for the `epd_gray` format and `false` otherwise.
+    grayscale: Option<bool>,
 }
 
 impl ImageQuery {
@@ -49,17 +61,70 @@ async fn image(
     format: Path<ImageFormat>,
     query: Query<ImageQuery>,
 ) -> actix_web::Result<HttpResponse> {
-    let mut data = state.weather.get().await?;
-    let mut rng = query.seed_rng();
+    let location = state
+        .location(DEFAULT_LOCATION)
+        .ok_or_else(|| ErrorNotFound("default location not configured"))?;
+
+    render_image(location, format.into_inner(), &query).await
+}
+
+#[get("/image/{location}.{format}")]
+async fn location_image(
+    state: Data<AppState>,
+    path: Path<(String, ImageFormat)>,
+    query: Query<ImageQuery>,
+) -> actix_web::Result<HttpResponse> {
+    let (name, format) = path.into_inner();
+    let location = state
+        .location(&name)
+        .ok_or_else(|| ErrorNotFound(format!("unknown location: {name}")))?;
+
+    render_image(location, format, &query).await
+}
 
-    if query.wreck_havoc {
-        weather::wreck_havoc(&mut data, &mut rng);
+async fn render_image(
+    location: &LocationState,
+    format: ImageFormat,
+    query: &ImageQuery,
+) -> actix_web::Result<HttpResponse> {
+    let mut rng = query.seed_rng();
+    let instant = query.at.unwrap_or_else(Timestamp::now);
+
+    let data = match query.scenario {
+        Some(scenario) => scenario.generate(
+            &location.location,
+            instant,
+            location.weather.hours(),
+            &mut rng,
+        )?,
+        None => {
+            let mut data = location.weather.get().await?;
+
+            if query.at.is_some() {
+                data.shift_to(instant)?;
+            }
+
+            data
+        }
+    };
+
+    if data.stale {
+        tracing::warn!(fetched_at = %data.fetched_at, "rendering stale weather data");
     }
 
-    let image = state.renderer.render(&data, rng);
-    let (body, mime_type) = image.encode(format.into_inner())?;
+    let grayscale = query
+        .grayscale
+        .unwrap_or(matches!(format, ImageFormat::EpdGray));
+    let image =
+        location
+            .renderer
+            .render(&data, instant, query.display, query.layout, grayscale, rng)?;
+    let (body, mime_type) = image.encode(format)?;
 
-    state.metrics.image_counter(mime_type.essence_str()).inc();
+    location
+        .metrics
+        .image_counter(mime_type.essence_str())
+        .inc();
 
     Ok(HttpResponse::Ok()
         .insert_header(ContentType(mime_type))
@@ -74,14 +139,27 @@ async fn run() -> Result<()> {
         .endpoint("/metrics")
         .build()?;
 
+    if let Some(dir) = &config.sprites.dir {
+        load_sprites(dir)?;
+
+        if config.sprites.watch {
+            actix_web::rt::spawn(watch_sprites(dir.clone()));
+        }
+    }
+
     let metrics = Metrics::new(&namespace, &prometheus.registry)?;
     let state = AppState::new(&config, metrics)?;
 
+    for location in state.locations() {
+        actix_web::rt::spawn(location.weather.clone().refresh_periodically());
+    }
+
     HttpServer::new(move || {
         App::new()
             .app_data(Data::new(state.clone()))
             .wrap(prometheus.clone())
             .service(image)
+            .service(location_image)
             .service(healthz)
             .wrap(Logger::default().exclude("/healthz").exclude("/metrics"))
     })
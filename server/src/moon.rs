@@ -0,0 +1,333 @@
+//! Helpers to calculate the illumination and rise and set times of the moon.
+//!
+//! The calculations follow the simplified formulas from Jean Meeus' "Astronomical Algorithms"
+//! as used by the [SunCalc](https://github.com/mourner/suncalc) library. Rise and set times are
+//! off by a few minutes typically and by up to about 20 minutes around the quarters, which is
+//! still less than an hour on the timeline.
+use jiff::{SignedDuration, Timestamp};
+use std::f64::consts::PI;
+
+const RAD: f64 = PI / 180.0;
+
+// Obliquity of the ecliptic.
+const OBLIQUITY: f64 = RAD * 23.4397;
+
+// Distance between earth and sun in kilometers.
+const SUN_DISTANCE: f64 = 149_598_000.0;
+
+// Altitude of the moon's center at rise and set, accounting for the moon's apparent radius.
+const RISE_SET_ALTITUDE: f64 = RAD * 0.133;
+
+// How far ahead to look for rise and set times. The moon rises and sets about every 25 hours
+// except close to the poles.
+const SEARCH_HOURS: i64 = 48;
+
+/// The phase of the moon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoonPhase {
+    New,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+    Full,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+}
+
+/// The illuminated part of the moon at a certain time.
+#[derive(Debug, Clone, Copy)]
+pub struct Illumination {
+    /// Illuminated fraction of the moon's disk, between 0.0 (new moon) and 1.0 (full moon).
+    pub fraction: f64,
+    /// Position within the lunar cycle between 0.0 and 1.0, where 0.0 is new moon, 0.25 first
+    /// quarter, 0.5 full moon and 0.75 last quarter.
+    pub cycle: f64,
+}
+
+impl Illumination {
+    /// Returns the [`MoonPhase`] the lunar cycle is in.
+    pub fn phase(&self) -> MoonPhase {
+        use MoonPhase::*;
+
+        const PHASES: [MoonPhase; 8] = [
+            New,
+            WaxingCrescent,
+            FirstQuarter,
+            WaxingGibbous,
+            Full,
+            WaningGibbous,
+            LastQuarter,
+            WaningCrescent,
+        ];
+
+        // Each phase spans an eighth of the cycle, centered around its nominal position.
+        let index = ((self.cycle * 8.0).round() as usize) % PHASES.len();
+        PHASES[index]
+    }
+}
+
+/// Provides the illumination and the rise and set times of the moon for a certain location.
+#[derive(Debug, Clone, Copy)]
+pub struct Moon {
+    lat: f64,
+    lon: f64,
+}
+
+impl Moon {
+    /// Creates a new `Moon` for the location at `lat`/`lon`.
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Moon { lat, lon }
+    }
+
+    /// Calculates the illumination of the moon at `ts`. The illumination is the same for every
+    /// location.
+    pub fn illumination(&self, ts: Timestamp) -> Illumination {
+        let d = days(ts);
+        let sun = sun_coords(d);
+        let moon = moon_coords(d);
+
+        let phi = (sun.dec.sin() * moon.dec.sin()
+            + sun.dec.cos() * moon.dec.cos() * (sun.ra - moon.ra).cos())
+        .acos();
+        let inc = (SUN_DISTANCE * phi.sin()).atan2(moon.dist - SUN_DISTANCE * phi.cos());
+        let angle = (sun.dec.cos() * (sun.ra - moon.ra).sin()).atan2(
+            sun.dec.sin() * moon.dec.cos()
+                - sun.dec.cos() * moon.dec.sin() * (sun.ra - moon.ra).cos(),
+        );
+
+        let sign = if angle < 0.0 { -1.0 } else { 1.0 };
+
+        Illumination {
+            fraction: (1.0 + inc.cos()) / 2.0,
+            cycle: 0.5 + 0.5 * inc * sign / PI,
+        }
+    }
+
+    /// Calculates the next moonrise after `ts`. Returns `None` if the moon does not rise within
+    /// the next two days, which may happen close to the poles.
+    pub fn next_rise(&self, ts: Timestamp) -> Option<Timestamp> {
+        self.next_crossing(ts, true)
+    }
+
+    /// Calculates the next moonset after `ts`. Returns `None` if the moon does not set within
+    /// the next two days, which may happen close to the poles.
+    #[cfg(test)]
+    pub fn next_set(&self, ts: Timestamp) -> Option<Timestamp> {
+        self.next_crossing(ts, false)
+    }
+
+    // Returns the altitude of the moon's center above the horizon at `ts` in radians, corrected
+    // for atmospheric refraction.
+    fn altitude(&self, ts: Timestamp) -> f64 {
+        let d = days(ts);
+        let moon = moon_coords(d);
+        let phi = RAD * self.lat;
+        let hour_angle = sidereal_time(d, RAD * -self.lon) - moon.ra;
+
+        let h = (phi.sin() * moon.dec.sin() + phi.cos() * moon.dec.cos() * hour_angle.cos()).asin();
+        h + refraction(h)
+    }
+
+    // Finds the next time after `ts` at which the moon crosses the horizon, going up if `rising`
+    // is `true` and going down otherwise.
+    //
+    // The altitude is sampled in steps of one hour. Every two hours, a quadratic is fitted
+    // through the last three samples to find the crossings in between.
+    fn next_crossing(&self, ts: Timestamp, rising: bool) -> Option<Timestamp> {
+        let at = |hours: i64| ts.checked_add(SignedDuration::from_hours(hours)).ok();
+        let altitude = |hours: i64| Some(self.altitude(at(hours)?) - RISE_SET_ALTITUDE);
+
+        let mut h0 = altitude(0)?;
+
+        for i in (1..SEARCH_HOURS).step_by(2) {
+            let h1 = altitude(i)?;
+            let h2 = altitude(i + 1)?;
+
+            let a = (h0 + h2) / 2.0 - h1;
+            let b = (h2 - h0) / 2.0;
+            let discriminant = b * b - 4.0 * a * h1;
+
+            h0 = h2;
+
+            if a == 0.0 || discriminant < 0.0 {
+                continue;
+            }
+
+            let xe = -b / (2.0 * a);
+            let dx = discriminant.sqrt() / (a.abs() * 2.0);
+
+            for x in [xe - dx, xe + dx] {
+                // The slope tells whether the moon goes up or down at the crossing.
+                let slope = 2.0 * a * x + b;
+
+                if x.abs() <= 1.0 && (slope > 0.0) == rising {
+                    let offset = SignedDuration::from_secs_f64((i as f64 + x) * 3600.0);
+                    let crossing = ts.checked_add(offset).ok()?;
+
+                    if crossing > ts {
+                        return Some(crossing);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// Equatorial coordinates of a celestial body.
+struct Coords {
+    // Right ascension.
+    ra: f64,
+    // Declination.
+    dec: f64,
+    // Distance in kilometers.
+    dist: f64,
+}
+
+// Returns the number of days since J2000.0.
+fn days(ts: Timestamp) -> f64 {
+    const SECONDS_DAY: f64 = 24.0 * 60.0 * 60.0;
+    const J1970: f64 = 2440588.0;
+    const J2000: f64 = 2451545.0;
+
+    ts.as_millisecond() as f64 / 1000.0 / SECONDS_DAY - 0.5 + J1970 - J2000
+}
+
+fn right_ascension(l: f64, b: f64) -> f64 {
+    (l.sin() * OBLIQUITY.cos() - b.tan() * OBLIQUITY.sin()).atan2(l.cos())
+}
+
+fn declination(l: f64, b: f64) -> f64 {
+    (b.sin() * OBLIQUITY.cos() + b.cos() * OBLIQUITY.sin() * l.sin()).asin()
+}
+
+fn sidereal_time(d: f64, lw: f64) -> f64 {
+    RAD * (280.16 + 360.9856235 * d) - lw
+}
+
+fn refraction(h: f64) -> f64 {
+    // The formula only works for positive altitudes.
+    let h = h.max(0.0);
+    0.0002967 / (h + 0.00312536 / (h + 0.08901179)).tan()
+}
+
+fn sun_coords(d: f64) -> Coords {
+    let mean_anomaly = RAD * (357.5291 + 0.98560028 * d);
+    let center = RAD
+        * (1.9148 * mean_anomaly.sin()
+            + 0.02 * (2.0 * mean_anomaly).sin()
+            + 0.0003 * (3.0 * mean_anomaly).sin());
+    let perihelion = RAD * 102.9372;
+    let longitude = mean_anomaly + center + perihelion + PI;
+
+    Coords {
+        ra: right_ascension(longitude, 0.0),
+        dec: declination(longitude, 0.0),
+        dist: SUN_DISTANCE,
+    }
+}
+
+fn moon_coords(d: f64) -> Coords {
+    // Mean ecliptic longitude, mean anomaly and argument of latitude.
+    let l = RAD * (218.316 + 13.176396 * d);
+    let m = RAD * (134.963 + 13.064993 * d);
+    let f = RAD * (93.272 + 13.229350 * d);
+
+    let longitude = l + RAD * 6.289 * m.sin();
+    let latitude = RAD * 5.128 * f.sin();
+
+    Coords {
+        ra: right_ascension(longitude, latitude),
+        dec: declination(longitude, latitude),
+        dist: 385001.0 - 20905.0 * m.cos(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn assert_close(a: Timestamp, b: Timestamp, minutes: i64) {
+        let diff = a.duration_since(b).abs();
+        assert!(
+            diff <= SignedDuration::from_mins(minutes),
+            "{a} and {b} are {diff:#} apart"
+        );
+    }
+
+    #[test]
+    fn illumination() {
+        use MoonPhase::*;
+
+        let moon = Moon::new(52.52, 13.405);
+
+        // Lunar phases of October 2024 published by the USNO.
+        let new = moon.illumination(ts("2024-10-02T18:49:00Z"));
+        assert!(new.fraction < 0.01);
+        assert_eq!(new.phase(), New);
+
+        let first_quarter = moon.illumination(ts("2024-10-10T18:55:00Z"));
+        assert!((first_quarter.fraction - 0.5).abs() < 0.03);
+        assert_eq!(first_quarter.phase(), FirstQuarter);
+
+        let full = moon.illumination(ts("2024-10-17T11:26:00Z"));
+        assert!(full.fraction > 0.99);
+        assert!((full.cycle - 0.5).abs() < 0.01);
+        assert_eq!(full.phase(), Full);
+
+        let last_quarter = moon.illumination(ts("2024-10-24T08:03:00Z"));
+        assert!((last_quarter.fraction - 0.5).abs() < 0.03);
+        assert_eq!(last_quarter.phase(), LastQuarter);
+
+        assert_eq!(
+            moon.illumination(ts("2024-10-06T12:00:00Z")).phase(),
+            WaxingCrescent
+        );
+        assert_eq!(
+            moon.illumination(ts("2024-10-28T12:00:00Z")).phase(),
+            WaningCrescent
+        );
+    }
+
+    #[test]
+    fn rise_and_set() {
+        let moon = Moon::new(52.52, 13.405);
+
+        // Reference times for Berlin, rounded to the minute. They were calculated with the full
+        // set of perturbation terms of the lunar theory and parallax, using the USNO's definition
+        // of rise and set: the upper limb touches the horizon with 34' of refraction.
+        let full_moon = ts("2024-10-17T11:26:00Z");
+        let rise = moon.next_rise(full_moon).unwrap();
+        assert_close(rise, ts("2024-10-17T15:54:00Z"), 5);
+        let set = moon.next_set(full_moon).unwrap();
+        assert_close(set, ts("2024-10-18T07:01:00Z"), 5);
+        assert_close(moon.next_rise(rise).unwrap(), ts("2024-10-18T16:10:00Z"), 5);
+
+        let first_quarter = ts("2024-10-10T00:00:00Z");
+        assert_close(
+            moon.next_rise(first_quarter).unwrap(),
+            ts("2024-10-10T13:51:00Z"),
+            5,
+        );
+
+        // The moon's center is slightly above the horizon at rise and set.
+        for ts in [rise, set] {
+            assert!((moon.altitude(ts) - RISE_SET_ALTITUDE).abs() < RAD * 0.1);
+        }
+    }
+
+    #[test]
+    fn circumpolar() {
+        // Around the full moon in December, the moon does not set north of the polar circle.
+        let moon = Moon::new(78.22, 15.65);
+        let date = ts("2024-12-15T12:00:00Z");
+
+        assert!(moon.next_set(date).is_none());
+    }
+}
@@ -1,7 +1,32 @@
 //! Helpers to calculate the time of sun phases.
-use jiff::{Timestamp, ToSpan};
+use jiff::{SignedDuration, Timestamp, ToSpan};
 pub use sun::SunPhase;
 
+// Altitude of the sun's center in degrees at sunrise and sunset, accounting for refraction and
+// the sun's apparent radius.
+const SUNRISE_ALTITUDE: f64 = -0.833;
+
+// Altitude of the sun in degrees at which civil twilight begins and ends.
+const CIVIL_TWILIGHT_ALTITUDE: f64 = -6.0;
+
+// Altitude of the sun in degrees at which astronomical twilight begins and ends.
+const ASTRONOMICAL_TWILIGHT_ALTITUDE: f64 = -18.0;
+
+// How many days to look ahead for the next occurrence of a sun phase. Even at the poles, every
+// phase happens at least once a year.
+const MAX_SEARCH_DAYS: i64 = 366;
+
+/// Whether the sun rises and sets on a certain day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Daylight {
+    /// The sun rises and sets.
+    Normal,
+    /// The sun stays above the horizon all day, also known as midnight sun.
+    PolarDay,
+    /// The sun stays below the horizon all day.
+    PolarNight,
+}
+
 /// Provides the timestamps of sun phases for a certain location.
 #[derive(Debug, Clone, Copy)]
 pub struct Sun {
@@ -18,32 +43,65 @@ impl Sun {
 
     /// Calculates the time for the next [`SunPhase`] relative to the given date. The returned
     /// `Timestamp` is guaranteed to be greater that `ts`.
-    pub fn next_phase(&self, ts: Timestamp, phase: SunPhase) -> Timestamp {
-        let phase_ts = self.phase(ts, phase);
-        if phase_ts > ts {
-            return phase_ts;
-        }
-
-        let next_day = ts.checked_add(24.hours()).expect("timestamp overflow");
-        self.phase(next_day, phase)
+    ///
+    /// Returns `None` if the phase does not happen within the next year, which is only possible
+    /// at the poles.
+    pub fn next_phase(&self, ts: Timestamp, phase: SunPhase) -> Option<Timestamp> {
+        (0..MAX_SEARCH_DAYS)
+            .map_while(|days| ts.checked_add((days * 24).hours()).ok())
+            .filter_map(|day| self.phase(day, phase))
+            .find(|&phase_ts| phase_ts > ts)
     }
 
     /// Calculates the time for the given [`SunPhase`] at a given date.
-    pub fn phase(&self, ts: Timestamp, phase: SunPhase) -> Timestamp {
+    ///
+    /// Returns `None` if the phase does not happen on that date, e.g. there's no sunrise during
+    /// polar night.
+    pub fn phase(&self, ts: Timestamp, phase: SunPhase) -> Option<Timestamp> {
         let now_ms = ts.as_millisecond();
         let phase_ms =
             sun::time_at_phase(now_ms, phase, self.lat, self.lon, self.alt.unwrap_or(0.0));
-        Timestamp::from_millisecond(phase_ms).expect("timestamp out of bounds")
+        let phase_ts = Timestamp::from_millisecond(phase_ms).ok()?;
+
+        // If the sun does not reach the altitude of the phase on that date, the calculation
+        // yields a bogus time far away from `ts`.
+        let distance = phase_ts.duration_since(ts).abs();
+        (distance < SignedDuration::from_hours(36)).then_some(phase_ts)
     }
 
-    /// Returns `true` if `ts` is between the [`SunPhase`]s given by `start` and `end`.
-    ///
-    /// The `end` [`SunPhase`] needs to happens after `start`, this method will always return
-    /// `false`.
-    pub fn is_between(&self, ts: Timestamp, start: SunPhase, end: SunPhase) -> bool {
-        let start_ts = self.phase(ts, start);
-        let end_ts = self.phase(ts, end);
-        start_ts < ts && ts < end_ts
+    /// Returns whether the sun rises and sets on the date of `ts`.
+    pub fn daylight(&self, ts: Timestamp) -> Daylight {
+        let sunrise = self.phase(ts, SunPhase::Sunrise);
+        let sunset = self.phase(ts, SunPhase::Sunset);
+
+        if sunrise.is_some() && sunset.is_some() {
+            Daylight::Normal
+        } else if self.altitude(ts) > SUNRISE_ALTITUDE {
+            Daylight::PolarDay
+        } else {
+            Daylight::PolarNight
+        }
+    }
+
+    /// Returns the altitude of the sun's center above the horizon at `ts` in degrees.
+    pub fn altitude(&self, ts: Timestamp) -> f64 {
+        sun::pos(ts.as_millisecond(), self.lat, self.lon)
+            .altitude
+            .to_degrees()
+    }
+
+    /// Returns `true` if the sun is below the horizon at `ts`, but not far enough for it to be
+    /// astronomical night. This is the time between [`SunPhase::Sunset`] and [`SunPhase::Night`]
+    /// and between [`SunPhase::NightEnd`] and [`SunPhase::Sunrise`] on days where those happen.
+    pub fn is_twilight(&self, ts: Timestamp) -> bool {
+        let altitude = self.altitude(ts);
+        altitude < SUNRISE_ALTITUDE && altitude > ASTRONOMICAL_TWILIGHT_ALTITUDE
+    }
+
+    /// Returns `true` if it's darker than civil twilight at `ts`. This is the time between
+    /// [`SunPhase::Dusk`] and [`SunPhase::Dawn`] on days where those happen.
+    pub fn is_dark(&self, ts: Timestamp) -> bool {
+        self.altitude(ts) < CIVIL_TWILIGHT_ALTITUDE
     }
 }
 
@@ -55,6 +113,13 @@ mod test {
         s.parse().unwrap()
     }
 
+    fn assert_on_day(actual: Option<Timestamp>, day: &str) {
+        let actual = actual.unwrap();
+        let start = ts(&format!("{day}T00:00:00Z"));
+        let end = start.checked_add(24.hours()).unwrap();
+        assert!(start <= actual && actual < end, "{actual} is not on {day}");
+    }
+
     #[test]
     fn phases() {
         use SunPhase::*;
@@ -63,26 +128,115 @@ mod test {
         let date = ts("2024-10-25T15:14:00Z");
 
         // Phase did not happen yet on `date`.
-        assert_eq!(sun.phase(date, Sunset), ts("2024-10-25T15:54:39.775Z"));
-        assert_eq!(sun.next_phase(date, Sunset), ts("2024-10-25T15:54:39.775Z"));
+        assert_eq!(
+            sun.phase(date, Sunset),
+            Some(ts("2024-10-25T15:54:39.775Z"))
+        );
+        assert_eq!(
+            sun.next_phase(date, Sunset),
+            Some(ts("2024-10-25T15:54:39.775Z"))
+        );
 
         // Phase already happened on `date`.
-        assert_eq!(sun.phase(date, Dawn), ts("2024-10-25T05:16:54.881Z"));
-        assert_eq!(sun.next_phase(date, Dawn), ts("2024-10-26T05:18:36.694Z"));
+        assert_eq!(sun.phase(date, Dawn), Some(ts("2024-10-25T05:16:54.881Z")));
+        assert_eq!(
+            sun.next_phase(date, Dawn),
+            Some(ts("2024-10-26T05:18:36.694Z"))
+        );
+
+        assert_eq!(sun.daylight(date), Daylight::Normal);
     }
 
     #[test]
-    fn is_between() {
-        use SunPhase::*;
-
+    fn twilight_and_darkness() {
         let sun = Sun::new(52.0, 13.0, None);
+
+        // Before sunset.
         let date = ts("2024-10-25T15:14:00Z");
+        assert!(!sun.is_twilight(date));
+        assert!(!sun.is_dark(date));
+
+        // Between sunset and dusk.
+        let date = ts("2024-10-25T16:10:00Z");
+        assert!(sun.is_twilight(date));
+        assert!(!sun.is_dark(date));
+
+        // Between night and night end.
+        let date = ts("2024-10-25T22:00:00Z");
+        assert!(!sun.is_twilight(date));
+        assert!(sun.is_dark(date));
+
+        // Between night end and dawn.
+        let date = ts("2024-10-25T05:00:00Z");
+        assert!(sun.is_twilight(date));
+        assert!(sun.is_dark(date));
+    }
+
+    #[test]
+    fn polar_night_tromso() {
+        use SunPhase::*;
+
+        let sun = Sun::new(69.6492, 18.9553, None);
+        let date = ts("2024-12-15T11:00:00Z");
+
+        assert_eq!(sun.daylight(date), Daylight::PolarNight);
+        assert_eq!(sun.phase(date, Sunrise), None);
+        assert_eq!(sun.phase(date, Sunset), None);
+        // The sun gets close enough to the horizon for civil twilight around noon.
+        assert!(sun.phase(date, Dawn).is_some());
+        assert!(sun.is_twilight(date));
+        assert!(!sun.is_dark(date));
+        // The sun returns in mid January.
+        assert_on_day(sun.next_phase(date, Sunrise), "2025-01-15");
+        assert_on_day(sun.next_phase(date, Sunset), "2025-01-15");
+    }
+
+    #[test]
+    fn midnight_sun_tromso() {
+        use SunPhase::*;
+
+        let sun = Sun::new(69.6492, 18.9553, None);
+        let date = ts("2024-06-21T00:00:00Z");
+
+        assert_eq!(sun.daylight(date), Daylight::PolarDay);
+        assert_eq!(sun.phase(date, Sunset), None);
+        assert_eq!(sun.phase(date, Dusk), None);
+        assert!(!sun.is_twilight(date));
+        assert!(!sun.is_dark(date));
+        assert_on_day(sun.next_phase(date, Sunset), "2024-07-26");
+    }
+
+    #[test]
+    fn polar_night_svalbard() {
+        use SunPhase::*;
+
+        let sun = Sun::new(78.2232, 15.6267, None);
+        let date = ts("2024-12-21T11:00:00Z");
+
+        assert_eq!(sun.daylight(date), Daylight::PolarNight);
+        assert_eq!(sun.phase(date, Sunrise), None);
+        // Not even civil twilight at noon, but it's not astronomical night either.
+        assert_eq!(sun.phase(date, Dawn), None);
+        assert!(sun.is_twilight(date));
+        assert!(sun.is_dark(date));
+        assert_on_day(sun.next_phase(date, Sunrise), "2025-02-16");
+
+        // Deep night later that day.
+        let date = ts("2024-12-21T23:00:00Z");
+        assert!(!sun.is_twilight(date));
+        assert!(sun.is_dark(date));
+    }
+
+    #[test]
+    fn midnight_sun_svalbard() {
+        use SunPhase::*;
+
+        let sun = Sun::new(78.2232, 15.6267, None);
+        let date = ts("2024-06-21T00:00:00Z");
 
-        assert!(sun.is_between(date, NightEnd, Night));
-        assert!(!sun.is_between(date, Sunset, Dusk));
-        assert!(!sun.is_between(date, Dusk, Night));
-        assert!(sun.is_between(date, Sunrise, Sunset));
-        // Sunrise always happens before sunset, so the date cannot be inbetween the two.
-        assert!(!sun.is_between(date, Sunset, Sunrise));
+        assert_eq!(sun.daylight(date), Daylight::PolarDay);
+        assert_eq!(sun.phase(date, Sunset), None);
+        assert!(!sun.is_dark(date));
+        assert_on_day(sun.next_phase(date, Sunset), "2024-08-25");
     }
 }
@@ -0,0 +1,111 @@
+//! Weather provider that serves met.no locationforecast documents from disk.
+use super::{WeatherData, WeatherProvider};
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use jiff::{RoundMode, Timestamp, TimestampRound, Unit};
+use monsoon::body::Body;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::debug;
+
+/// Loads weather data from saved met.no locationforecast JSON documents.
+///
+/// If the configured path is a directory, all `.json` files in it are served in lexical order,
+/// one per fetch, starting over after the last one. The timestamps of the weather data are
+/// shifted so that the first time series entry lines up with the current hour.
+#[derive(Debug)]
+pub struct Fixture {
+    paths: Vec<PathBuf>,
+    next: AtomicUsize,
+}
+
+impl Fixture {
+    /// Creates a new fixture provider for the JSON document or directory at `path`.
+    pub fn new(path: &Path) -> Result<Self> {
+        let paths = if path.is_dir() {
+            let mut paths = Vec::new();
+
+            for entry in path.read_dir()? {
+                let path = entry?.path();
+
+                if path.extension().is_some_and(|ext| ext == "json") {
+                    paths.push(path);
+                }
+            }
+
+            paths.sort();
+            paths
+        } else {
+            vec![path.to_owned()]
+        };
+
+        if paths.is_empty() {
+            return Err(Error::new(format!(
+                "no weather fixtures found in {}",
+                path.display()
+            )));
+        }
+
+        Ok(Fixture {
+            paths,
+            next: AtomicUsize::new(0),
+        })
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for Fixture {
+    async fn fetch(&self, hours: usize) -> Result<WeatherData> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.paths.len();
+        let path = &self.paths[index];
+
+        debug!(path = %path.display(), "loading weather fixture");
+
+        let buf = tokio::fs::read(path).await?;
+        let body: Body = serde_json::from_slice(&buf)?;
+        let mut data = WeatherData::from_body(&body, hours)?;
+
+        let now = Timestamp::now().round(
+            TimestampRound::new()
+                .smallest(Unit::Hour)
+                .mode(RoundMode::Trunc),
+        )?;
+
+        data.shift(now.duration_since(data.current.timestamp))?;
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn fetch() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/weather/testdata");
+        let fixture = Fixture::new(&path.join("metno_locationforecast.json")).unwrap();
+        let data = fixture.fetch(24).await.unwrap();
+
+        let now = Timestamp::now();
+        let current = data.current.timestamp;
+
+        assert!(current <= now);
+        assert!(now.duration_since(current).as_secs() < 3600);
+        assert_eq!(data.forecasts.len(), 24);
+        assert_eq!(
+            data.forecasts[0]
+                .timestamp
+                .duration_since(current)
+                .as_secs(),
+            3600
+        );
+        assert_eq!(data.current.air_temperature, 11.3);
+    }
+
+    #[test]
+    fn empty_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Fixture::new(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! Store of past weather observations.
+use super::DataPoint;
+use crate::error::Result;
+use jiff::{SignedDuration, Timestamp};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+// Name of the file in the state directory which holds the observations.
+const HISTORY_FILE: &str = "history.json";
+
+/// The weather observed at a certain time, i.e. the current weather at the time it was fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    pub timestamp: Timestamp,
+    pub air_temperature: f64,
+}
+
+impl From<&DataPoint> for Observation {
+    fn from(data_point: &DataPoint) -> Self {
+        Observation {
+            timestamp: data_point.timestamp,
+            air_temperature: data_point.air_temperature,
+        }
+    }
+}
+
+/// Keeps the observations of the last `retention` in chronological order.
+///
+/// If a state directory is configured, the observations are persisted there after every change
+/// and loaded again on startup.
+#[derive(Debug)]
+pub struct History {
+    path: Option<PathBuf>,
+    retention: SignedDuration,
+    observations: Mutex<Vec<Observation>>,
+}
+
+impl History {
+    /// Creates a new `History` which keeps observations for `retention`. If `state_dir` is set,
+    /// observations persisted by a previous run are loaded from there.
+    pub fn new(state_dir: Option<&Path>, retention: SignedDuration) -> Result<Self> {
+        let path = state_dir
+            .map(|dir| {
+                std::fs::create_dir_all(dir)?;
+                Ok::<_, std::io::Error>(dir.join(HISTORY_FILE))
+            })
+            .transpose()?;
+
+        let observations = path
+            .as_deref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                read(path)
+                    .inspect_err(|err| warn!("failed to load weather history: {err}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        debug!(count = observations.len(), "loaded weather history");
+
+        Ok(History {
+            path,
+            retention,
+            observations: Mutex::new(observations),
+        })
+    }
+
+    /// Returns all observations which were not pruned yet.
+    pub fn observations(&self) -> Vec<Observation> {
+        self.observations.lock().unwrap().clone()
+    }
+
+    /// Adds `observation` and prunes all observations older than the retention period relative to
+    /// `now`. Returns the remaining observations.
+    ///
+    /// Providers report the weather for the start of the current hour, so fetching multiple times
+    /// per hour yields the same timestamp. In that case, the newer observation replaces the older
+    /// one.
+    pub fn record(&self, observation: Observation, now: Timestamp) -> Vec<Observation> {
+        let mut observations = self.observations.lock().unwrap();
+
+        observations.retain(|o| o.timestamp < observation.timestamp);
+        observations.push(observation);
+
+        if let Ok(cutoff) = now.checked_sub(self.retention) {
+            observations.retain(|o| o.timestamp >= cutoff);
+        }
+
+        let persisted = self.path.as_deref().map(|path| write(path, &observations));
+
+        if let Some(Err(err)) = persisted {
+            warn!("failed to persist weather history: {err}");
+        }
+
+        observations.clone()
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<Observation>> {
+    let buf = std::fs::read(path)?;
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+// Replaces the file atomically, so that a crash never leaves a partially written history behind.
+fn write(path: &Path, observations: &[Observation]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec(observations)?)?;
+    std::fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn observation(timestamp: &str, air_temperature: f64) -> Observation {
+        Observation {
+            timestamp: ts(timestamp),
+            air_temperature,
+        }
+    }
+
+    #[test]
+    fn record_and_prune() {
+        let history = History::new(None, SignedDuration::from_hours(3)).unwrap();
+
+        history.record(
+            observation("2025-06-14T10:00:00Z", 18.0),
+            ts("2025-06-14T10:10:00Z"),
+        );
+        history.record(
+            observation("2025-06-14T11:00:00Z", 19.0),
+            ts("2025-06-14T11:10:00Z"),
+        );
+        // Same hour, newer data.
+        history.record(
+            observation("2025-06-14T11:00:00Z", 19.5),
+            ts("2025-06-14T11:40:00Z"),
+        );
+
+        assert_eq!(
+            history.observations(),
+            [
+                observation("2025-06-14T10:00:00Z", 18.0),
+                observation("2025-06-14T11:00:00Z", 19.5),
+            ]
+        );
+
+        // The first observation is past the retention period.
+        let observations = history.record(
+            observation("2025-06-14T13:00:00Z", 21.0),
+            ts("2025-06-14T13:10:00Z"),
+        );
+
+        assert_eq!(
+            observations,
+            [
+                observation("2025-06-14T11:00:00Z", 19.5),
+                observation("2025-06-14T13:00:00Z", 21.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let retention = SignedDuration::from_hours(24);
+
+        let history = History::new(Some(dir.path()), retention).unwrap();
+        history.record(
+            observation("2025-06-14T10:00:00Z", 18.0),
+            ts("2025-06-14T10:10:00Z"),
+        );
+
+        let history = History::new(Some(dir.path()), retention).unwrap();
+        assert_eq!(
+            history.observations(),
+            [observation("2025-06-14T10:00:00Z", 18.0)]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Official weather warnings from the met.no MetAlerts API.
+use super::USER_AGENT;
+use crate::error::Result;
+use jiff::Timestamp;
+use reqwest::Client;
+use serde::Deserialize;
+use tracing::debug;
+
+/// An official weather warning, e.g. for storm, ice, flood or extreme heat.
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub severity: Severity,
+    /// The time at which the event is expected to begin.
+    pub onset: Timestamp,
+    /// The time at which the event is expected to end.
+    pub expires: Timestamp,
+}
+
+/// Severity of an [`Alert`] as defined by CAP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub enum Severity {
+    #[serde(other)]
+    Unknown,
+    Minor,
+    Moderate,
+    Severe,
+    Extreme,
+}
+
+/// Fetches the CAP alerts published by met.no for a location in GeoJSON format.
+#[derive(Debug)]
+pub struct MetAlerts {
+    client: Client,
+    url: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+impl MetAlerts {
+    /// Creates a new MetAlerts client for the location at `latitude`/`longitude` using the API
+    /// at `base_url`.
+    pub fn new(base_url: &str, latitude: f64, longitude: f64) -> Result<Self> {
+        let client = Client::builder().user_agent(USER_AGENT).build()?;
+
+        Ok(MetAlerts {
+            client,
+            url: format!(
+                "{}/weatherapi/metalerts/2.0/current.json",
+                base_url.trim_end_matches('/')
+            ),
+            latitude,
+            longitude,
+        })
+    }
+
+    /// Fetches all alerts for the location which did not expire yet, including those which only
+    /// begin in the future.
+    pub async fn fetch(&self) -> Result<Vec<Alert>> {
+        self.fetch_at(Timestamp::now()).await
+    }
+
+    async fn fetch_at(&self, now: Timestamp) -> Result<Vec<Alert>> {
+        // Met.no filters the alerts by the areas which contain the coordinates.
+        let query = [
+            ("lat", format!("{:.4}", self.latitude)),
+            ("lon", format!("{:.4}", self.longitude)),
+        ];
+
+        let collection: FeatureCollection = self
+            .client
+            .get(&self.url)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let alerts: Vec<Alert> = collection
+            .features
+            .into_iter()
+            .map(Feature::into_alert)
+            .filter(|alert| alert.expires > now)
+            .collect();
+
+        debug!(count = alerts.len(), "fetched weather alerts");
+
+        Ok(alerts)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FeatureCollection {
+    features: Vec<Feature>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Feature {
+    properties: Properties,
+    when: When,
+}
+
+impl Feature {
+    fn into_alert(self) -> Alert {
+        let (onset, expires) = self.when.interval;
+
+        Alert {
+            severity: self.properties.severity,
+            onset,
+            expires,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Properties {
+    severity: Severity,
+}
+
+#[derive(Debug, Deserialize)]
+struct When {
+    interval: (Timestamp, Timestamp),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path, query_param},
+    };
+
+    const CURRENT: &str = include_str!("testdata/metalerts_current.json");
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    async fn server() -> MockServer {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/weatherapi/metalerts/2.0/current.json"))
+            .and(query_param("lat", "62.4722"))
+            .and(query_param("lon", "6.1495"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(CURRENT, "application/json"))
+            .mount(&server)
+            .await;
+
+        server
+    }
+
+    #[tokio::test]
+    async fn fetch() {
+        let server = server().await;
+        let client = MetAlerts::new(&server.uri(), 62.47225, 6.14949).unwrap();
+        let alerts = client.fetch_at(ts("2025-01-16T15:00:00Z")).await.unwrap();
+
+        // The expired alert is skipped.
+        assert_eq!(alerts.len(), 2);
+
+        let gale = &alerts[0];
+        assert_eq!(gale.severity, Severity::Moderate);
+        assert_eq!(gale.onset, ts("2025-01-16T12:00:00Z"));
+        assert_eq!(gale.expires, ts("2025-01-17T06:00:00Z"));
+
+        let icing = &alerts[1];
+        assert_eq!(icing.severity, Severity::Severe);
+        assert_eq!(icing.onset, ts("2025-01-17T03:00:00Z"));
+    }
+
+    #[tokio::test]
+    async fn fetch_all_expired() {
+        let server = server().await;
+        let client = MetAlerts::new(&server.uri(), 62.47225, 6.14949).unwrap();
+        let alerts = client.fetch_at(ts("2025-01-18T00:00:00Z")).await.unwrap();
+
+        assert!(alerts.is_empty());
+    }
+}
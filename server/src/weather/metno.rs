@@ -0,0 +1,404 @@
+//! Weather provider backed by the met.no locationforecast API.
+use super::{
+    USER_AGENT, WeatherData, WeatherProvider,
+    recording::{Recorder, Recording},
+};
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use jiff::{Timestamp, fmt::rfc2822};
+use monsoon::body::Body;
+use reqwest::{
+    Client, Response, StatusCode,
+    header::{EXPIRES, HeaderName, IF_MODIFIED_SINCE, LAST_MODIFIED},
+};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tower::{
+    Service, ServiceBuilder, ServiceExt,
+    limit::{ConcurrencyLimit, RateLimit},
+};
+use tracing::{debug, warn};
+
+// Name of the file in the state directory which holds the last response.
+const CACHE_FILE: &str = "metno_last_response.json";
+
+/// A raw locationforecast response body together with its cache validators.
+#[derive(Debug, Clone)]
+pub(super) struct RawResponse {
+    pub(super) body: String,
+    pub(super) expires: Option<String>,
+    pub(super) last_modified: Option<String>,
+}
+
+impl RawResponse {
+    /// Reads the body and cache validators from `response`. The body of the `last` response is
+    /// reused if the data was not modified since.
+    async fn read(response: Response, last: Option<&RawResponse>) -> Result<Self> {
+        let header = |name: HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+
+        let expires = header(EXPIRES);
+        let last_modified = header(LAST_MODIFIED);
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            debug!("weather data not modified since last request");
+
+            let last =
+                last.ok_or_else(|| Error::new("unexpected 304 response without prior response"))?;
+
+            return Ok(RawResponse {
+                body: last.body.clone(),
+                expires: expires.or_else(|| last.expires.clone()),
+                last_modified: last_modified.or_else(|| last.last_modified.clone()),
+            });
+        }
+
+        let body = response.text().await?;
+
+        Ok(RawResponse {
+            body,
+            expires,
+            last_modified,
+        })
+    }
+
+    /// Parses the response body into `WeatherData` with `hours` of forecasts.
+    fn weather_data(&self, hours: usize) -> Result<WeatherData> {
+        let body: Body = serde_json::from_str(&self.body)?;
+        let mut data = WeatherData::from_body(&body, hours)?;
+        data.expires = self.expires_at();
+        Ok(data)
+    }
+
+    /// Returns the time at which the response expires, if known.
+    fn expires_at(&self) -> Option<Timestamp> {
+        let expires = self.expires.as_deref()?;
+        rfc2822::DateTimeParser::new()
+            .parse_timestamp(expires)
+            .inspect_err(|err| warn!("invalid Expires header {expires:?}: {err}"))
+            .ok()
+    }
+}
+
+#[derive(Debug)]
+struct MetNoInner {
+    client: Client,
+    service: ConcurrencyLimit<RateLimit<Client>>,
+    url: String,
+    recorder: Option<Recorder>,
+    cache_path: Option<PathBuf>,
+    // The last response, whose Last-Modified header makes the next request conditional.
+    last_response: Option<RawResponse>,
+    latitude: f64,
+    longitude: f64,
+    altitude: Option<i32>,
+}
+
+impl MetNoInner {
+    fn new(
+        base_url: &str,
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<i32>,
+        record_dir: Option<&Path>,
+        state_dir: Option<&Path>,
+    ) -> Result<Self> {
+        let client = Client::builder().user_agent(USER_AGENT).build()?;
+
+        // Limit request volume according to the met.no TOS: https://api.met.no/doc/TermsOfService.
+        let service = ServiceBuilder::new()
+            .concurrency_limit(10)
+            .rate_limit(20, Duration::from_secs(1))
+            .service(client.clone());
+
+        let recorder = record_dir.map(Recorder::new).transpose()?;
+
+        let cache_path = state_dir
+            .map(|dir| {
+                std::fs::create_dir_all(dir)?;
+                Ok::<_, Error>(dir.join(CACHE_FILE))
+            })
+            .transpose()?;
+
+        Ok(MetNoInner {
+            client,
+            service,
+            url: format!(
+                "{}/weatherapi/locationforecast/2.0/complete",
+                base_url.trim_end_matches('/')
+            ),
+            recorder,
+            cache_path,
+            last_response: None,
+            latitude,
+            longitude,
+            altitude,
+        })
+    }
+
+    // Loads the last response from the cache file, if there is any for the current location.
+    // Its validators make the first request after a restart conditional.
+    fn load_cache(&mut self) -> Option<Recording> {
+        let path = self.cache_path.as_ref().filter(|path| path.exists())?;
+
+        let recording = Recording::read(path)
+            .inspect_err(|err| warn!("failed to load cached weather response: {err}"))
+            .ok()?;
+
+        if !recording.is_for(self.latitude, self.longitude, self.altitude) {
+            debug!("ignoring cached weather response for different location");
+            return None;
+        }
+
+        debug!(path = %path.display(), "loaded cached weather response");
+
+        self.last_response = Some(recording.to_raw_response());
+
+        Some(recording)
+    }
+
+    // Saves the last response to the cache file so that it survives restarts.
+    fn save_cache(&self, response: &RawResponse) -> Result<()> {
+        let Some(path) = &self.cache_path else {
+            return Ok(());
+        };
+
+        Recording::new(response, self.latitude, self.longitude, self.altitude)?.write(path)
+    }
+
+    async fn get(&mut self, hours: usize) -> Result<WeatherData> {
+        // Met.no asks to not use more than four decimals for coordinates.
+        let mut query = vec![
+            ("lat", format!("{:.4}", self.latitude)),
+            ("lon", format!("{:.4}", self.longitude)),
+        ];
+
+        if let Some(altitude) = self.altitude {
+            query.push(("altitude", altitude.to_string()));
+        }
+
+        let mut request = self.client.get(&self.url).query(&query);
+
+        if let Some(last_modified) = self
+            .last_response
+            .as_ref()
+            .and_then(|last| last.last_modified.as_deref())
+        {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let response = self
+            .service
+            .ready()
+            .await?
+            .call(request.build()?)
+            .await?
+            .error_for_status()?;
+
+        let raw = RawResponse::read(response, self.last_response.as_ref()).await?;
+
+        let recorded = self
+            .recorder
+            .as_ref()
+            .map(|recorder| recorder.record(&raw, self.latitude, self.longitude, self.altitude));
+
+        if let Some(Err(err)) = recorded {
+            warn!("failed to record weather response: {err}");
+        }
+
+        let data = raw.weather_data(hours)?;
+
+        if let Err(err) = self.save_cache(&raw) {
+            warn!("failed to cache weather response: {err}");
+        }
+
+        self.last_response = Some(raw);
+
+        Ok(data)
+    }
+}
+
+/// Fetches weather data from [api.met.no](https://api.met.no).
+#[derive(Debug)]
+pub struct MetNo {
+    inner: Mutex<MetNoInner>,
+    // The response loaded from the cache on startup.
+    cached: Option<Recording>,
+}
+
+impl MetNo {
+    /// Creates a new met.no provider for the location at `latitude`/`longitude` with optional
+    /// altitude using the API at `base_url`.
+    ///
+    /// If `record_dir` is set, every response is saved to that directory so that it can be
+    /// replayed later. If `state_dir` is set, the last response is persisted there and loaded
+    /// again on startup.
+    pub fn new(
+        base_url: &str,
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<i32>,
+        record_dir: Option<&Path>,
+        state_dir: Option<&Path>,
+    ) -> Result<Self> {
+        let mut inner = MetNoInner::new(
+            base_url, latitude, longitude, altitude, record_dir, state_dir,
+        )?;
+        let cached = inner.load_cache();
+
+        Ok(MetNo {
+            inner: Mutex::new(inner),
+            cached,
+        })
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for MetNo {
+    async fn fetch(&self, hours: usize) -> Result<WeatherData> {
+        self.inner.lock().await.get(hours).await
+    }
+
+    fn cached(&self, hours: usize) -> Option<WeatherData> {
+        let recording = self.cached.as_ref()?;
+
+        let mut data = recording
+            .to_raw_response()
+            .weather_data(hours)
+            .inspect_err(|err| warn!("failed to parse cached weather response: {err}"))
+            .ok()?;
+
+        data.fetched_at = recording.timestamp;
+
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method, path, query_param},
+    };
+
+    const BASE_URL: &str = "https://api.met.no";
+
+    fn response() -> RawResponse {
+        RawResponse {
+            body: include_str!("testdata/metno_locationforecast.json").to_owned(),
+            expires: Some("Sat, 14 Jun 2025 12:30:00 GMT".to_owned()),
+            last_modified: Some("Sat, 14 Jun 2025 12:00:00 GMT".to_owned()),
+        }
+    }
+
+    #[test]
+    fn restore_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let metno = MetNo::new(BASE_URL, 52.52, 13.405, Some(34), None, Some(dir.path())).unwrap();
+        assert!(metno.cached(24).is_none());
+
+        let before = Timestamp::now();
+        metno
+            .inner
+            .try_lock()
+            .unwrap()
+            .save_cache(&response())
+            .unwrap();
+
+        // The last response survives a restart.
+        let restarted =
+            MetNo::new(BASE_URL, 52.52, 13.405, Some(34), None, Some(dir.path())).unwrap();
+        let data = restarted.cached(24).unwrap();
+        assert_eq!(data.current.air_temperature, 11.3);
+        assert_eq!(data.forecasts.len(), 24);
+        assert_eq!(data.expires, Some("2025-06-14T12:30:00Z".parse().unwrap()));
+        assert!(data.fetched_at >= before);
+        assert!(!data.stale);
+    }
+
+    #[test]
+    fn ignore_cache_for_other_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let metno = MetNo::new(BASE_URL, 52.52, 13.405, Some(34), None, Some(dir.path())).unwrap();
+        metno
+            .inner
+            .try_lock()
+            .unwrap()
+            .save_cache(&response())
+            .unwrap();
+
+        for (latitude, longitude, altitude) in [
+            (48.137, 13.405, Some(34)),
+            (52.52, 11.575, Some(34)),
+            (52.52, 13.405, None),
+        ] {
+            let other = MetNo::new(
+                BASE_URL,
+                latitude,
+                longitude,
+                altitude,
+                None,
+                Some(dir.path()),
+            )
+            .unwrap();
+            assert!(other.cached(24).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn conditional_request_after_restart() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let metno = MetNo::new(
+            &server.uri(),
+            52.52,
+            13.405,
+            Some(34),
+            None,
+            Some(dir.path()),
+        )
+        .unwrap();
+        metno
+            .inner
+            .try_lock()
+            .unwrap()
+            .save_cache(&response())
+            .unwrap();
+
+        Mock::given(method("GET"))
+            .and(path("/weatherapi/locationforecast/2.0/complete"))
+            .and(query_param("lat", "52.5200"))
+            .and(query_param("lon", "13.4050"))
+            .and(query_param("altitude", "34"))
+            .and(header("If-Modified-Since", "Sat, 14 Jun 2025 12:00:00 GMT"))
+            .respond_with(
+                ResponseTemplate::new(304)
+                    .insert_header("Expires", "Sat, 14 Jun 2025 13:00:00 GMT"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // The first request after a restart is conditional and the cached body is reused.
+        let restarted = MetNo::new(
+            &server.uri(),
+            52.52,
+            13.405,
+            Some(34),
+            None,
+            Some(dir.path()),
+        )
+        .unwrap();
+        let data = restarted.fetch(24).await.unwrap();
+        assert_eq!(data.current.air_temperature, 11.3);
+        assert_eq!(data.expires, Some("2025-06-14T13:00:00Z".parse().unwrap()));
+    }
+}
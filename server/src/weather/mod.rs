@@ -0,0 +1,928 @@
+mod fixture;
+mod history;
+mod metalerts;
+mod metno;
+mod open_meteo;
+mod recording;
+mod scenario;
+
+pub use self::history::Observation;
+pub use self::metalerts::{Alert, Severity};
+pub use self::scenario::Scenario;
+use self::{
+    fixture::Fixture, history::History, metalerts::MetAlerts, metno::MetNo, open_meteo::OpenMeteo,
+    recording::Replay,
+};
+use crate::{
+    config::{Config, DEFAULT_LOCATION, Location, ProviderConfig},
+    error::{Error, Result},
+};
+use async_trait::async_trait;
+use jiff::{SignedDuration, Timestamp};
+use monsoon::body::{Body, TimeSeries};
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use std::{iter, str::FromStr};
+use tracing::{debug, warn};
+
+// Met.no requires to identify oneself via user-agent header. This is best practice anyways.
+const USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("CARGO_PKG_REPOSITORY"),
+    ")"
+);
+
+// Refresh interval used if the provider did not tell when its data expires.
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// Lower bound for the refresh interval to avoid hammering the provider with requests.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+// Delay before retrying a failed refresh.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A source of weather data.
+///
+/// Implementations are expected to handle their own synchronization and rate limiting since
+/// `fetch` may be called concurrently from multiple request handlers.
+#[async_trait]
+pub trait WeatherProvider: Debug + Send + Sync {
+    /// Fetches the current weather and `hours` of hourly forecasts.
+    async fn fetch(&self, hours: usize) -> Result<WeatherData>;
+
+    /// Returns weather data with `hours` of hourly forecasts that was persisted by a previous run,
+    /// if any. It is served until the first fetch succeeds.
+    fn cached(&self, _hours: usize) -> Option<WeatherData> {
+        None
+    }
+}
+
+/// Handle to the configured [`WeatherProvider`] and the latest weather data fetched from it.
+#[derive(Debug, Clone)]
+pub struct Weather {
+    provider: Arc<dyn WeatherProvider>,
+    alerts: Option<Arc<MetAlerts>>,
+    history: Option<Arc<History>>,
+    latest: Arc<RwLock<Option<WeatherData>>>,
+    max_age: SignedDuration,
+    hours: usize,
+}
+
+impl Weather {
+    /// Creates a new `Weather` which fetches its data from `provider`.
+    ///
+    /// The weather data contains forecasts for the next `hours`. If the provider fails, the last
+    /// successfully fetched data is served for up to `max_age`.
+    pub fn new<P>(provider: P, hours: usize, max_age: SignedDuration) -> Self
+    where
+        P: WeatherProvider + 'static,
+    {
+        let latest = provider.cached(hours);
+
+        Weather {
+            provider: Arc::new(provider),
+            alerts: None,
+            history: None,
+            latest: Arc::new(RwLock::new(latest)),
+            max_age,
+            hours,
+        }
+    }
+
+    /// Attaches the alerts fetched from `alerts` to the weather data on every refresh.
+    pub fn with_alerts(mut self, alerts: MetAlerts) -> Self {
+        self.alerts = Some(Arc::new(alerts));
+        self
+    }
+
+    /// Records the current weather of every refresh in `history` and attaches the past
+    /// observations to the weather data.
+    pub fn with_history(mut self, history: History) -> Self {
+        if let Some(latest) = self.latest.write().unwrap().as_mut() {
+            latest.history = history.observations();
+        }
+
+        self.history = Some(Arc::new(history));
+        self
+    }
+
+    /// Creates a new `Weather` for the location called `name` using the provider selected in the
+    /// config.
+    ///
+    /// Directories for recordings and state of named locations are subdirectories of the
+    /// configured ones, so that the default location keeps using the configured directories as-is.
+    pub fn from_config(config: &Config, name: &str, location: &Location) -> Result<Self> {
+        let hours = config.forecast_hours;
+        let max_age = config.max_data_age;
+
+        let location_dir = |dir: &Path| {
+            if name == DEFAULT_LOCATION {
+                dir.to_owned()
+            } else {
+                dir.join(name)
+            }
+        };
+
+        let weather = match &config.provider {
+            ProviderConfig::MetNo {
+                record_dir,
+                base_url,
+            } => Weather::new(
+                MetNo::new(
+                    base_url,
+                    location.latitude,
+                    location.longitude,
+                    location.altitude,
+                    record_dir.as_deref().map(location_dir).as_deref(),
+                    config.state_dir.as_deref().map(location_dir).as_deref(),
+                )?,
+                hours,
+                max_age,
+            ),
+            ProviderConfig::OpenMeteo { base_url } => Weather::new(
+                OpenMeteo::new(
+                    base_url,
+                    location.latitude,
+                    location.longitude,
+                    location.altitude,
+                )?,
+                hours,
+                max_age,
+            ),
+            ProviderConfig::Fixture { path } => Weather::new(Fixture::new(path)?, hours, max_age),
+            ProviderConfig::Replay { path, at } => {
+                Weather::new(Replay::new(&location_dir(path), *at)?, hours, max_age)
+            }
+        };
+
+        let weather = if config.history.enabled {
+            let state_dir = config.state_dir.as_deref().map(location_dir);
+            let history = History::new(state_dir.as_deref(), config.history.retention)?;
+            weather.with_history(history)
+        } else {
+            weather
+        };
+
+        if !config.alerts.enabled {
+            return Ok(weather);
+        }
+
+        let alerts = MetAlerts::new(
+            &config.alerts.base_url,
+            location.latitude,
+            location.longitude,
+        )?;
+
+        Ok(weather.with_alerts(alerts))
+    }
+
+    /// Returns the number of hours of forecasts in the weather data.
+    pub fn hours(&self) -> usize {
+        self.hours
+    }
+
+    /// Returns the latest weather data. The data is only fetched from the provider if there is
+    /// none yet, e.g. because the first background refresh did not finish yet.
+    ///
+    /// Returns an error if the latest data is older than the configured maximum age.
+    pub async fn get(&self) -> Result<WeatherData> {
+        let latest = self.latest.read().unwrap().clone();
+
+        let Some(data) = latest else {
+            return self.refresh().await;
+        };
+
+        let age = Timestamp::now().duration_since(data.fetched_at);
+
+        if age > self.max_age {
+            return Err(Error::new(format!(
+                "latest weather data is too old: fetched {} hours ago",
+                age.as_hours()
+            )));
+        }
+
+        Ok(data)
+    }
+
+    /// Fetches weather data from the provider and makes it the latest. If fetching fails, the
+    /// previous data is marked as stale.
+    ///
+    /// If alerts are configured, they are fetched as well. Failing to fetch alerts is not fatal,
+    /// the previously fetched alerts are kept instead. If a history is configured, the current
+    /// weather is recorded in it.
+    async fn refresh(&self) -> Result<WeatherData> {
+        match self.provider.fetch(self.hours).await {
+            Ok(mut data) => {
+                data.fetched_at = Timestamp::now();
+                data.alerts = self.fetch_alerts().await;
+
+                if let Some(history) = &self.history {
+                    data.history = history.record((&data.current).into(), data.fetched_at);
+                }
+
+                *self.latest.write().unwrap() = Some(data.clone());
+                Ok(data)
+            }
+            Err(err) => {
+                if let Some(latest) = self.latest.write().unwrap().as_mut() {
+                    latest.stale = true;
+                }
+
+                Err(err)
+            }
+        }
+    }
+
+    async fn fetch_alerts(&self) -> Vec<Alert> {
+        let Some(alerts) = &self.alerts else {
+            return Vec::new();
+        };
+
+        match alerts.fetch().await {
+            Ok(alerts) => alerts,
+            Err(err) => {
+                warn!("failed to fetch weather alerts: {err}");
+
+                self.latest
+                    .read()
+                    .unwrap()
+                    .as_ref()
+                    .map(|latest| latest.alerts.clone())
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    /// Refreshes the weather data in a loop. The next refresh is scheduled for when the data
+    /// expires.
+    pub async fn refresh_periodically(self) {
+        loop {
+            let delay = match self.refresh().await {
+                Ok(data) => refresh_delay(data.expires),
+                Err(err) => {
+                    warn!("failed to refresh weather data: {err}");
+                    RETRY_INTERVAL
+                }
+            };
+
+            debug!(?delay, "scheduling next weather refresh");
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+fn refresh_delay(expires: Option<Timestamp>) -> Duration {
+    let Some(expires) = expires else {
+        return DEFAULT_REFRESH_INTERVAL;
+    };
+
+    Duration::try_from(expires.duration_since(Timestamp::now()))
+        .unwrap_or_default()
+        .max(MIN_REFRESH_INTERVAL)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WeatherData {
+    pub coords: Coords,
+    pub current: DataPoint,
+    pub forecasts: Vec<DataPoint>,
+    /// The time after which the data should be refreshed, if the provider knows it.
+    pub expires: Option<Timestamp>,
+    /// The time at which the data was fetched from the provider.
+    pub fetched_at: Timestamp,
+    /// Whether fetching newer data failed.
+    pub stale: bool,
+    /// Official weather warnings which did not expire yet.
+    pub alerts: Vec<Alert>,
+    /// Past observations in chronological order, including the current weather.
+    pub history: Vec<Observation>,
+}
+
+impl WeatherData {
+    fn from_body(body: &Body, hours: usize) -> Result<WeatherData> {
+        let coords = Coords {
+            latitude: body.geometry.coordinates.latitude,
+            longitude: body.geometry.coordinates.longitude,
+            altitude: body.geometry.coordinates.altitude,
+        };
+
+        let data_points = body
+            .properties
+            .timeseries
+            .iter()
+            .map(DataPoint::from_time_series);
+
+        WeatherData::from_data_points(coords, data_points, hours)
+    }
+
+    /// Creates `WeatherData` from hourly data points. The first data point is the current
+    /// weather, the following `hours` are used as forecast data.
+    ///
+    /// Returns an error if there are not enough data points.
+    fn from_data_points<I>(coords: Coords, data_points: I, hours: usize) -> Result<WeatherData>
+    where
+        I: IntoIterator<Item = Result<DataPoint>>,
+    {
+        let mut data_points = data_points.into_iter();
+
+        let Some(current) = data_points.next().transpose()? else {
+            return Err(Error::new("empty time series"));
+        };
+
+        let forecasts = data_points.take(hours).collect::<Result<Vec<_>>>()?;
+
+        if forecasts.len() < hours {
+            return Err(Error::new("not enough forecast data"));
+        }
+
+        Ok(WeatherData {
+            coords,
+            current,
+            forecasts,
+            expires: None,
+            fetched_at: Timestamp::default(),
+            stale: false,
+            alerts: Vec::new(),
+            history: Vec::new(),
+        })
+    }
+
+    /// Shifts the data to the hour of `instant`, unless the data already covers it. This allows
+    /// rendering the scene for any instant with the data at hand. Instants within the forecast are
+    /// left to [`WeatherData::trim_to`], which keeps the forecast for that hour.
+    pub fn shift_to(&mut self, instant: Timestamp) -> Result<()> {
+        let last = self.forecasts.last().unwrap_or(&self.current).timestamp;
+
+        if self.current.timestamp <= instant
+            && instant.duration_since(last) < SignedDuration::from_hours(1)
+        {
+            return Ok(());
+        }
+
+        let offset = instant.duration_since(self.current.timestamp);
+
+        // Shift by whole hours, so that the data points stay on the full hour.
+        let hours = offset.as_secs().div_euclid(3600);
+
+        debug!(hours, "shifting weather data");
+
+        self.shift(SignedDuration::from_hours(hours))
+    }
+
+    /// Drops the data points which are already in the past at `instant`, so that stale data
+    /// starts at the right hour. The last forecast is always kept.
+    pub fn trim_to(&mut self, instant: Timestamp) {
+        let past = self
+            .forecasts
+            .iter()
+            .take(self.forecasts.len().saturating_sub(1))
+            .take_while(|forecast| forecast.timestamp <= instant)
+            .count();
+
+        if past == 0 {
+            return;
+        }
+
+        debug!(hours = past, "dropping past weather data");
+
+        self.current = self.forecasts.drain(..past).next_back().unwrap_or_default();
+    }
+
+    /// Shifts all timestamps by `offset`, including those of alerts and past observations.
+    fn shift(&mut self, offset: SignedDuration) -> Result<()> {
+        for data_point in iter::once(&mut self.current).chain(&mut self.forecasts) {
+            data_point.timestamp = data_point.timestamp.checked_add(offset)?;
+        }
+
+        for alert in &mut self.alerts {
+            alert.onset = alert.onset.checked_add(offset)?;
+            alert.expires = alert.expires.checked_add(offset)?;
+        }
+
+        for observation in &mut self.history {
+            observation.timestamp = observation.timestamp.checked_add(offset)?;
+        }
+
+        self.expires = self.expires.map(|ts| ts.checked_add(offset)).transpose()?;
+        self.fetched_at = self.fetched_at.checked_add(offset)?;
+
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default)]
+pub struct DataPoint {
+    pub air_pressure_at_sea_level: f64,
+    pub air_temperature: f64,
+    pub cloud_area_fraction: f64,
+    pub condition: Condition,
+    pub dew_point_temperature: Option<f64>,
+    pub fog_area_fraction: f64,
+    pub precipitation_amount: f64,
+    pub probability_of_thunder: f64,
+    pub relative_humidity: Option<f64>,
+    pub timestamp: Timestamp,
+    pub ultraviolet_index_clear_sky: Option<f64>,
+    pub wind_from_direction: f64,
+    pub wind_speed: f64,
+    pub wind_speed_of_gust: Option<f64>,
+    /// Summary of the next 6 hours, if the provider has one.
+    pub next_6_hours: Option<PeriodSummary>,
+    /// Summary of the next 12 hours, if the provider has one.
+    pub next_12_hours: Option<PeriodSummary>,
+}
+
+impl DataPoint {
+    fn from_time_series(series: &TimeSeries) -> Result<DataPoint> {
+        let timestamp = Timestamp::from_second(series.time.timestamp())?;
+
+        let parse_condition = |symbol_code: &str| {
+            Condition::from_str(symbol_code)
+                .inspect_err(|err| warn!("{err}"))
+                .ok()
+        };
+
+        let next_6_hours = series.data.next_6_hours.as_ref().map(|next| PeriodSummary {
+            condition: parse_condition(next.summary.symbol_code).unwrap_or_default(),
+            precipitation_amount: next
+                .details
+                .as_ref()
+                .and_then(|details| details.precipitation_amount),
+        });
+
+        let next_12_hours = series
+            .data
+            .next_12_hours
+            .as_ref()
+            .map(|next| PeriodSummary {
+                condition: parse_condition(next.summary.symbol_code).unwrap_or_default(),
+                ..Default::default()
+            });
+
+        let (condition, precipitation_amount, probability_of_thunder) = match &series
+            .data
+            .next_1_hours
+        {
+            Some(next) => {
+                let (precipitation_amount, probability_of_thunder) = next
+                    .details
+                    .as_ref()
+                    .map(|details| (details.precipitation_amount, details.probability_of_thunder))
+                    .unwrap_or_default();
+
+                (
+                    parse_condition(next.summary.symbol_code),
+                    precipitation_amount,
+                    probability_of_thunder.map(|probability| (probability / 100.0).clamp(0.0, 1.0)),
+                )
+            }
+            // Beyond the first couple of days met.no only provides 6-hour summaries. The
+            // precipitation is spread evenly over the hours of the period.
+            None => match &next_6_hours {
+                Some(summary) => (
+                    Some(summary.condition),
+                    summary.precipitation_amount.map(|amount| amount / 6.0),
+                    None,
+                ),
+                None => (None, None, None),
+            },
+        };
+
+        let details = &series.data.instant.details;
+
+        Ok(DataPoint {
+            air_pressure_at_sea_level: details.air_pressure_at_sea_level.unwrap_or_default(),
+            air_temperature: details.air_temperature.unwrap_or_default(),
+            cloud_area_fraction: details.cloud_area_fraction.unwrap_or_default(),
+            condition: condition.unwrap_or_default(),
+            dew_point_temperature: details.dew_point_temperature,
+            fog_area_fraction: details.fog_area_fraction.unwrap_or_default(),
+            precipitation_amount: precipitation_amount.unwrap_or_default(),
+            probability_of_thunder: probability_of_thunder.unwrap_or_default(),
+            relative_humidity: details.relative_humidity,
+            timestamp,
+            ultraviolet_index_clear_sky: details.ultraviolet_index_clear_sky,
+            wind_from_direction: details.wind_from_direction.unwrap_or_default(),
+            wind_speed: details.wind_speed.unwrap_or_default(),
+            wind_speed_of_gust: details.wind_speed_of_gust,
+            next_6_hours,
+            next_12_hours,
+        })
+    }
+}
+
+/// Summary of the weather over a period of several hours.
+#[derive(Debug, Clone, Default)]
+pub struct PeriodSummary {
+    pub condition: Condition,
+    pub precipitation_amount: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Coords {
+    pub longitude: f64,
+    pub latitude: f64,
+    pub altitude: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Condition {
+    ClearSky,
+    Cloudy,
+    Fair,
+    Fog,
+    PartlyCloudy,
+    Rain(Precipitation),
+    Sleet(Precipitation),
+    Snow(Precipitation),
+    #[default]
+    Unknown,
+}
+
+impl Condition {
+    /// Returns the kind of precipitation if the condition is rain, sleet or snow.
+    pub fn precipitation(&self) -> Option<Precipitation> {
+        match *self {
+            Condition::Rain(precipitation)
+            | Condition::Sleet(precipitation)
+            | Condition::Snow(precipitation) => Some(precipitation),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the condition includes thunder.
+    pub fn thunder(&self) -> bool {
+        self.precipitation()
+            .is_some_and(|precipitation| precipitation.thunder)
+    }
+
+    // Parses the precipitation part of a met.no symbol code, e.g. "lightrainshowersandthunder".
+    fn from_precipitation_code(code: &str) -> Option<Condition> {
+        let (intensity, code) = if let Some(code) = code.strip_prefix("light") {
+            (Intensity::Light, code)
+        } else if let Some(code) = code.strip_prefix("heavy") {
+            (Intensity::Heavy, code)
+        } else {
+            (Intensity::Normal, code)
+        };
+
+        // Typing errors in "lightssleetshowersandthunder" and "lightssnowshowersandthunder".
+        let code = code
+            .strip_prefix('s')
+            .filter(|code| code.starts_with('s'))
+            .unwrap_or(code);
+
+        let (code, thunder) = match code.strip_suffix("andthunder") {
+            Some(code) => (code, true),
+            None => (code, false),
+        };
+
+        let (code, showers) = match code.strip_suffix("showers") {
+            Some(code) => (code, true),
+            None => (code, false),
+        };
+
+        let precipitation = Precipitation {
+            intensity,
+            showers,
+            thunder,
+        };
+
+        match code {
+            "rain" => Some(Condition::Rain(precipitation)),
+            "sleet" => Some(Condition::Sleet(precipitation)),
+            "snow" => Some(Condition::Snow(precipitation)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // We don't distiguish between day, night and polar twilight conditions.
+        let code = s.split_once('_').map_or(s, |(code, _)| code);
+
+        // Conditions from https://github.com/metno/weathericons/tree/main/weather
+        let condition = match code {
+            "clearsky" => Condition::ClearSky,
+            "cloudy" => Condition::Cloudy,
+            "fair" => Condition::Fair,
+            "fog" => Condition::Fog,
+            "partlycloudy" => Condition::PartlyCloudy,
+            _ => Condition::from_precipitation_code(code)
+                .ok_or_else(|| Error::new(format!("unknown weather condition: {}", s)))?,
+        };
+
+        Ok(condition)
+    }
+}
+
+/// Details about rain, sleet or snow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Precipitation {
+    pub intensity: Intensity,
+    /// Precipitation comes in short bursts rather than continuously.
+    pub showers: bool,
+    /// The precipitation is accompanied by thunder.
+    pub thunder: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Intensity {
+    Light,
+    #[default]
+    Normal,
+    Heavy,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Serves the met.no test document until told to fail.
+    #[derive(Debug, Default)]
+    struct FlakyProvider {
+        fail: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl WeatherProvider for FlakyProvider {
+        async fn fetch(&self, hours: usize) -> Result<WeatherData> {
+            if self.fail.load(Ordering::Relaxed) {
+                return Err(Error::new("provider unavailable"));
+            }
+
+            let body: Body =
+                serde_json::from_str(include_str!("testdata/metno_locationforecast.json"))?;
+            WeatherData::from_body(&body, hours)
+        }
+    }
+
+    // All symbol codes from https://github.com/metno/weathericons/tree/main/weather without the
+    // day/night/polartwilight variants.
+    const SYMBOL_CODES: &[&str] = &[
+        "clearsky",
+        "cloudy",
+        "fair",
+        "fog",
+        "heavyrain",
+        "heavyrainandthunder",
+        "heavyrainshowers",
+        "heavyrainshowersandthunder",
+        "heavysleet",
+        "heavysleetandthunder",
+        "heavysleetshowers",
+        "heavysleetshowersandthunder",
+        "heavysnow",
+        "heavysnowandthunder",
+        "heavysnowshowers",
+        "heavysnowshowersandthunder",
+        "lightrain",
+        "lightrainandthunder",
+        "lightrainshowers",
+        "lightrainshowersandthunder",
+        "lightsleet",
+        "lightsleetandthunder",
+        "lightsleetshowers",
+        "lightsnow",
+        "lightsnowandthunder",
+        "lightsnowshowers",
+        "lightssleetshowersandthunder",
+        "lightssnowshowersandthunder",
+        "partlycloudy",
+        "rain",
+        "rainandthunder",
+        "rainshowers",
+        "rainshowersandthunder",
+        "sleet",
+        "sleetandthunder",
+        "sleetshowers",
+        "sleetshowersandthunder",
+        "snow",
+        "snowandthunder",
+        "snowshowers",
+        "snowshowersandthunder",
+    ];
+
+    #[test]
+    fn optional_details_and_summaries() {
+        let mut doc: serde_json::Value =
+            serde_json::from_str(include_str!("testdata/metno_locationforecast.json")).unwrap();
+
+        let timeseries = &mut doc["properties"]["timeseries"];
+        timeseries[0]["data"]["instant"]["details"]["wind_speed_of_gust"] = 9.1.into();
+        timeseries[0]["data"]["instant"]["details"]["ultraviolet_index_clear_sky"] = 1.5.into();
+        timeseries[1]["data"]["next_1_hours"].take();
+        timeseries[1]["data"]["next_6_hours"] = serde_json::json!({
+            "summary": { "symbol_code": "heavyrain" },
+            "details": { "precipitation_amount": 12.0 }
+        });
+
+        let body: Body = serde_json::from_value(doc).unwrap();
+        let data = WeatherData::from_body(&body, 24).unwrap();
+
+        let current = &data.current;
+        assert_eq!(current.relative_humidity, Some(72.0));
+        assert_eq!(current.dew_point_temperature, Some(8.2));
+        assert_eq!(current.wind_speed_of_gust, Some(9.1));
+        assert_eq!(current.ultraviolet_index_clear_sky, Some(1.5));
+        assert_eq!(current.condition, Condition::PartlyCloudy);
+        let next_6_hours = current.next_6_hours.as_ref().unwrap();
+        assert_eq!(next_6_hours.condition, Condition::Cloudy);
+        assert_eq!(next_6_hours.precipitation_amount, Some(0.0));
+        let next_12_hours = current.next_12_hours.as_ref().unwrap();
+        assert!(matches!(next_12_hours.condition, Condition::Rain(_)));
+
+        // Without `next_1_hours` the 6-hour summary is used.
+        let fallback = &data.forecasts[0];
+        assert!(matches!(
+            fallback.condition,
+            Condition::Rain(Precipitation {
+                intensity: Intensity::Heavy,
+                ..
+            })
+        ));
+        assert_eq!(fallback.precipitation_amount, 2.0);
+    }
+
+    #[test]
+    fn all_symbol_codes() {
+        for code in SYMBOL_CODES {
+            let condition = Condition::from_str(code).unwrap();
+            assert_ne!(condition, Condition::Unknown, "{code}");
+
+            for suffix in ["_day", "_night", "_polartwilight"] {
+                assert_eq!(
+                    Condition::from_str(&format!("{code}{suffix}")).unwrap(),
+                    condition
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn precipitation_details() {
+        assert_eq!(
+            Condition::from_str("heavyrainshowersandthunder_day").unwrap(),
+            Condition::Rain(Precipitation {
+                intensity: Intensity::Heavy,
+                showers: true,
+                thunder: true,
+            })
+        );
+        assert_eq!(
+            Condition::from_str("lightssnowshowersandthunder_night").unwrap(),
+            Condition::Snow(Precipitation {
+                intensity: Intensity::Light,
+                showers: true,
+                thunder: true,
+            })
+        );
+        assert_eq!(
+            Condition::from_str("sleet").unwrap(),
+            Condition::Sleet(Precipitation::default())
+        );
+        assert!(!Condition::from_str("lightsnowshowers").unwrap().thunder());
+        assert!(
+            Condition::from_str("fair_polartwilight")
+                .unwrap()
+                .precipitation()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn unknown_symbol_codes() {
+        assert!(Condition::from_str("lightfog").is_err());
+        assert!(Condition::from_str("hail").is_err());
+        assert!(Condition::from_str("heavyrainshowersandlightning").is_err());
+    }
+
+    #[test]
+    fn shift_to() {
+        let body: Body =
+            serde_json::from_str(include_str!("testdata/metno_locationforecast.json")).unwrap();
+        let mut data = WeatherData::from_body(&body, 24).unwrap();
+        let current = data.current.timestamp;
+        let first_forecast = data.forecasts[0].timestamp;
+
+        // The current weather already covers the instant.
+        let instant = current.checked_add(SignedDuration::from_mins(30)).unwrap();
+        data.shift_to(instant).unwrap();
+        assert_eq!(data.current.timestamp, current);
+
+        // Instants within the forecast are covered as well.
+        let last_forecast = data.forecasts[23].timestamp;
+        for mins in [90, 23 * 60, 24 * 60 + 59] {
+            let instant = current
+                .checked_add(SignedDuration::from_mins(mins))
+                .unwrap();
+            data.shift_to(instant).unwrap();
+            assert_eq!(data.current.timestamp, current);
+            assert_eq!(data.forecasts[23].timestamp, last_forecast);
+        }
+
+        // Shift back by a bit more than two days.
+        let instant = current
+            .checked_sub(SignedDuration::from_mins(49 * 60 + 10))
+            .unwrap();
+        data.shift_to(instant).unwrap();
+
+        let offset = SignedDuration::from_hours(-50);
+        assert_eq!(data.current.timestamp, current.checked_add(offset).unwrap());
+        assert_eq!(
+            data.forecasts[0].timestamp,
+            first_forecast.checked_add(offset).unwrap()
+        );
+    }
+
+    #[test]
+    fn trim_to() {
+        let body: Body =
+            serde_json::from_str(include_str!("testdata/metno_locationforecast.json")).unwrap();
+        let mut data = WeatherData::from_body(&body, 24).unwrap();
+        let current = data.current.timestamp;
+
+        // Nothing to drop within the hour of the current weather.
+        data.trim_to(current.checked_add(SignedDuration::from_mins(59)).unwrap());
+        assert_eq!(data.current.timestamp, current);
+        assert_eq!(data.forecasts.len(), 24);
+
+        let instant = current.checked_add(SignedDuration::from_mins(150)).unwrap();
+        data.trim_to(instant);
+        assert_eq!(
+            data.current.timestamp,
+            current.checked_add(SignedDuration::from_hours(2)).unwrap()
+        );
+        assert_eq!(data.forecasts.len(), 22);
+        assert!(data.forecasts[0].timestamp > instant);
+
+        // The last forecast is kept.
+        data.trim_to(current.checked_add(SignedDuration::from_hours(48)).unwrap());
+        assert_eq!(data.forecasts.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn get_rejects_data_older_than_max_age() {
+        let weather = Weather::new(FlakyProvider::default(), 24, SignedDuration::from_hours(6));
+        let mut data = weather.refresh().await.unwrap();
+
+        data.fetched_at = Timestamp::now() - SignedDuration::from_hours(5);
+        *weather.latest.write().unwrap() = Some(data.clone());
+        assert!(weather.get().await.is_ok());
+
+        data.fetched_at = Timestamp::now() - SignedDuration::from_hours(7);
+        *weather.latest.write().unwrap() = Some(data);
+        assert!(weather.get().await.is_err());
+    }
+
+    #[test]
+    fn refresh_delay_from_expires() {
+        assert_eq!(refresh_delay(None), DEFAULT_REFRESH_INTERVAL);
+
+        let past = Timestamp::now() - SignedDuration::from_mins(5);
+        assert_eq!(refresh_delay(Some(past)), MIN_REFRESH_INTERVAL);
+
+        // Expiring sooner than the minimum interval is clamped.
+        let soon = Timestamp::now() + SignedDuration::from_secs(10);
+        assert_eq!(refresh_delay(Some(soon)), MIN_REFRESH_INTERVAL);
+
+        let future = Timestamp::now() + SignedDuration::from_mins(30);
+        let delay = refresh_delay(Some(future));
+        assert!(delay <= Duration::from_secs(30 * 60));
+        assert!(delay > Duration::from_secs(29 * 60));
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_keeps_previous_data() {
+        let provider = FlakyProvider::default();
+        let fail = provider.fail.clone();
+        let weather = Weather::new(provider, 24, SignedDuration::from_hours(6));
+
+        let data = weather.refresh().await.unwrap();
+        assert!(!data.stale);
+
+        fail.store(true, Ordering::Relaxed);
+        assert!(weather.refresh().await.is_err());
+
+        let stale = weather.get().await.unwrap();
+        assert!(stale.stale);
+        assert_eq!(stale.fetched_at, data.fetched_at);
+        assert_eq!(stale.current.timestamp, data.current.timestamp);
+
+        // The next successful refresh clears the marker.
+        fail.store(false, Ordering::Relaxed);
+        weather.refresh().await.unwrap();
+        assert!(!weather.get().await.unwrap().stale);
+    }
+}
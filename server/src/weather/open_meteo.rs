@@ -0,0 +1,340 @@
+//! Weather provider backed by the [Open-Meteo](https://open-meteo.com) forecast API.
+use super::{
+    Condition, Coords, DataPoint, Intensity, Precipitation, USER_AGENT, WeatherData,
+    WeatherProvider,
+};
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use jiff::Timestamp;
+use reqwest::Client;
+use serde::Deserialize;
+use tracing::warn;
+
+// The hourly variables requested from the forecast API.
+const HOURLY_VARIABLES: &str = "temperature_2m,cloud_cover,precipitation,weather_code,\
+     wind_speed_10m,wind_direction_10m,pressure_msl,visibility";
+
+// Visibility in meters below which we consider it foggy.
+const FOG_VISIBILITY: f64 = 1000.0;
+
+/// Fetches weather data from the Open-Meteo forecast API.
+#[derive(Debug)]
+pub struct OpenMeteo {
+    client: Client,
+    url: String,
+    latitude: f64,
+    longitude: f64,
+    altitude: Option<i32>,
+}
+
+impl OpenMeteo {
+    /// Creates a new Open-Meteo provider for the location at `latitude`/`longitude` with optional
+    /// altitude using the API at `base_url`.
+    pub fn new(
+        base_url: &str,
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<i32>,
+    ) -> Result<Self> {
+        let client = Client::builder().user_agent(USER_AGENT).build()?;
+
+        Ok(OpenMeteo {
+            client,
+            url: format!("{}/v1/forecast", base_url.trim_end_matches('/')),
+            latitude,
+            longitude,
+            altitude,
+        })
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for OpenMeteo {
+    async fn fetch(&self, hours: usize) -> Result<WeatherData> {
+        // The current hour plus the forecast hours. We need one additional hour because Open-Meteo
+        // reports the precipitation sum of the preceding hour, while we need it for the next hour.
+        let forecast_hours = hours + 2;
+
+        let mut query = vec![
+            ("latitude", self.latitude.to_string()),
+            ("longitude", self.longitude.to_string()),
+            ("hourly", HOURLY_VARIABLES.to_owned()),
+            ("forecast_hours", forecast_hours.to_string()),
+            ("wind_speed_unit", "ms".to_owned()),
+            ("timeformat", "unixtime".to_owned()),
+        ];
+
+        if let Some(altitude) = self.altitude {
+            query.push(("elevation", altitude.to_string()));
+        }
+
+        let forecast: Forecast = self
+            .client
+            .get(&self.url)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        forecast.into_weather_data(hours)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Forecast {
+    latitude: f64,
+    longitude: f64,
+    elevation: f64,
+    hourly: Hourly,
+}
+
+impl Forecast {
+    fn into_weather_data(self, hours: usize) -> Result<WeatherData> {
+        let coords = Coords {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            altitude: self.elevation,
+        };
+
+        let hourly = &self.hourly;
+        let data_points = (0..hourly.time.len()).map(|i| hourly.data_point(i));
+
+        WeatherData::from_data_points(coords, data_points, hours)
+    }
+}
+
+// Hourly variables. Open-Meteo returns each variable as an array which is index-aligned with
+// `time`. Values may be `null` if the model has no data for a given hour.
+#[derive(Debug, Deserialize)]
+struct Hourly {
+    time: Vec<i64>,
+    temperature_2m: Vec<Option<f64>>,
+    cloud_cover: Vec<Option<f64>>,
+    precipitation: Vec<Option<f64>>,
+    weather_code: Vec<Option<u8>>,
+    wind_speed_10m: Vec<Option<f64>>,
+    wind_direction_10m: Vec<Option<f64>>,
+    pressure_msl: Vec<Option<f64>>,
+    visibility: Vec<Option<f64>>,
+}
+
+impl Hourly {
+    fn data_point(&self, i: usize) -> Result<DataPoint> {
+        fn value(values: &[Option<f64>], i: usize) -> f64 {
+            values.get(i).copied().flatten().unwrap_or_default()
+        }
+
+        let timestamp = Timestamp::from_second(self.time[i])?;
+
+        let code = self.weather_code.get(i).copied().flatten();
+
+        let condition = code
+            .and_then(|code| {
+                Condition::from_wmo_code(code)
+                    .inspect_err(|err| warn!("{err}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        let probability_of_thunder = match code {
+            // Slight or moderate thunderstorm.
+            Some(95) => 0.5,
+            // Thunderstorm with hail.
+            Some(96 | 99) => 1.0,
+            _ => 0.0,
+        };
+
+        let fog_area_fraction = self
+            .visibility
+            .get(i)
+            .copied()
+            .flatten()
+            .map(|visibility| ((1.0 - visibility / FOG_VISIBILITY) * 100.0).clamp(0.0, 100.0))
+            .unwrap_or_default();
+
+        Ok(DataPoint {
+            air_pressure_at_sea_level: value(&self.pressure_msl, i),
+            air_temperature: value(&self.temperature_2m, i),
+            cloud_area_fraction: value(&self.cloud_cover, i),
+            condition,
+            dew_point_temperature: None,
+            fog_area_fraction,
+            // The precipitation of the next hour is reported at the next timestamp.
+            precipitation_amount: value(&self.precipitation, i + 1),
+            probability_of_thunder,
+            relative_humidity: None,
+            timestamp,
+            ultraviolet_index_clear_sky: None,
+            wind_from_direction: value(&self.wind_direction_10m, i),
+            wind_speed: value(&self.wind_speed_10m, i),
+            wind_speed_of_gust: None,
+            next_6_hours: None,
+            next_12_hours: None,
+        })
+    }
+}
+
+impl Condition {
+    /// Maps a WMO weather interpretation code to a `Condition`.
+    fn from_wmo_code(code: u8) -> Result<Condition> {
+        use Intensity::*;
+
+        fn precipitation(intensity: Intensity, showers: bool, thunder: bool) -> Precipitation {
+            Precipitation {
+                intensity,
+                showers,
+                thunder,
+            }
+        }
+
+        // Codes from https://open-meteo.com/en/docs#weather_variable_documentation
+        let condition = match code {
+            0 => Condition::ClearSky,
+            1 => Condition::Fair,
+            2 => Condition::PartlyCloudy,
+            3 => Condition::Cloudy,
+            45 | 48 => Condition::Fog,
+            // Drizzle.
+            51 | 53 => Condition::Rain(precipitation(Light, false, false)),
+            55 => Condition::Rain(precipitation(Normal, false, false)),
+            // Freezing drizzle.
+            56 => Condition::Sleet(precipitation(Light, false, false)),
+            57 => Condition::Sleet(precipitation(Normal, false, false)),
+            // Rain.
+            61 => Condition::Rain(precipitation(Light, false, false)),
+            63 => Condition::Rain(precipitation(Normal, false, false)),
+            65 => Condition::Rain(precipitation(Heavy, false, false)),
+            // Freezing rain.
+            66 => Condition::Sleet(precipitation(Light, false, false)),
+            67 => Condition::Sleet(precipitation(Heavy, false, false)),
+            // Snow fall and snow grains.
+            71 | 77 => Condition::Snow(precipitation(Light, false, false)),
+            73 => Condition::Snow(precipitation(Normal, false, false)),
+            75 => Condition::Snow(precipitation(Heavy, false, false)),
+            // Rain showers.
+            80 => Condition::Rain(precipitation(Light, true, false)),
+            81 => Condition::Rain(precipitation(Normal, true, false)),
+            82 => Condition::Rain(precipitation(Heavy, true, false)),
+            // Snow showers.
+            85 => Condition::Snow(precipitation(Light, true, false)),
+            86 => Condition::Snow(precipitation(Heavy, true, false)),
+            // Thunderstorms, with hail for 96 and 99.
+            95 => Condition::Rain(precipitation(Normal, true, true)),
+            96 | 99 => Condition::Rain(precipitation(Heavy, true, true)),
+            _ => return Err(Error::new(format!("unknown WMO weather code: {code}"))),
+        };
+
+        Ok(condition)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path, query_param},
+    };
+
+    const FORECAST: &str = include_str!("testdata/open_meteo_forecast.json");
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    async fn server(body: &str) -> MockServer {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/forecast"))
+            .and(query_param("latitude", "52.52"))
+            .and(query_param("longitude", "13.41"))
+            .and(query_param("elevation", "38"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+            .mount(&server)
+            .await;
+
+        server
+    }
+
+    #[tokio::test]
+    async fn fetch() {
+        let server = server(FORECAST).await;
+        let provider = OpenMeteo::new(&server.uri(), 52.52, 13.41, Some(38)).unwrap();
+        let data = provider.fetch(24).await.unwrap();
+
+        assert_eq!(data.coords.latitude, 52.52);
+        assert_eq!(data.coords.longitude, 13.419998);
+        assert_eq!(data.coords.altitude, 38.0);
+        assert_eq!(data.forecasts.len(), 24);
+
+        let current = &data.current;
+        assert_eq!(current.timestamp, ts("2025-06-14T12:00:00Z"));
+        assert_eq!(current.air_temperature, 21.4);
+        assert_eq!(current.air_pressure_at_sea_level, 1016.2);
+        assert_eq!(current.cloud_area_fraction, 42.0);
+        assert!(matches!(current.condition, Condition::PartlyCloudy));
+        assert_eq!(current.wind_speed, 3.9);
+        assert_eq!(current.wind_from_direction, 245.0);
+        // Precipitation is shifted by one hour.
+        assert_eq!(current.precipitation_amount, 0.0);
+        assert_eq!(current.fog_area_fraction, 0.0);
+
+        let thunderstorm = &data.forecasts[5];
+        assert_eq!(thunderstorm.timestamp, ts("2025-06-14T18:00:00Z"));
+        assert!(thunderstorm.condition.thunder());
+        assert_eq!(thunderstorm.probability_of_thunder, 0.5);
+        assert_eq!(thunderstorm.precipitation_amount, 2.3);
+
+        let fog = &data.forecasts[17];
+        assert_eq!(fog.timestamp, ts("2025-06-15T06:00:00Z"));
+        assert!(matches!(fog.condition, Condition::Fog));
+        assert_eq!(fog.fog_area_fraction, 75.0);
+    }
+
+    #[tokio::test]
+    async fn fetch_not_enough_data() {
+        let mut forecast: serde_json::Value = serde_json::from_str(FORECAST).unwrap();
+
+        for values in forecast["hourly"].as_object_mut().unwrap().values_mut() {
+            values.as_array_mut().unwrap().truncate(12);
+        }
+
+        let server = server(&forecast.to_string()).await;
+        let provider = OpenMeteo::new(&server.uri(), 52.52, 13.41, Some(38)).unwrap();
+        let err = provider.fetch(24).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "not enough forecast data");
+    }
+
+    #[test]
+    fn wmo_codes() {
+        assert!(matches!(
+            Condition::from_wmo_code(0),
+            Ok(Condition::ClearSky)
+        ));
+        assert!(matches!(Condition::from_wmo_code(48), Ok(Condition::Fog)));
+        assert!(matches!(
+            Condition::from_wmo_code(57),
+            Ok(Condition::Sleet(Precipitation {
+                intensity: Intensity::Normal,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            Condition::from_wmo_code(86),
+            Ok(Condition::Snow(Precipitation {
+                intensity: Intensity::Heavy,
+                showers: true,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            Condition::from_wmo_code(99),
+            Ok(Condition::Rain(Precipitation { thunder: true, .. }))
+        ));
+        assert!(Condition::from_wmo_code(42).is_err());
+    }
+}
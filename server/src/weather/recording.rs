@@ -0,0 +1,269 @@
+//! Recording and replaying of raw met.no responses.
+use super::{WeatherData, WeatherProvider, metno::RawResponse};
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use jiff::Timestamp;
+use monsoon::body::Body;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::debug;
+
+/// A raw weather response together with the time and location it was fetched for.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct Recording {
+    pub(super) timestamp: Timestamp,
+    latitude: f64,
+    longitude: f64,
+    altitude: Option<i32>,
+    expires: Option<String>,
+    last_modified: Option<String>,
+    body: Box<RawValue>,
+}
+
+impl Recording {
+    /// Creates a new `Recording` of `response` fetched for the given location just now.
+    pub(super) fn new(
+        response: &RawResponse,
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<i32>,
+    ) -> Result<Self> {
+        Ok(Recording {
+            timestamp: Timestamp::now(),
+            latitude,
+            longitude,
+            altitude,
+            expires: response.expires.clone(),
+            last_modified: response.last_modified.clone(),
+            body: RawValue::from_string(response.body.clone())?,
+        })
+    }
+
+    /// Reads a `Recording` from the file at `path`.
+    pub(super) fn read(path: &Path) -> Result<Self> {
+        let buf = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&buf)?)
+    }
+
+    /// Writes the `Recording` to the file at `path`. The file is replaced atomically, so readers
+    /// never observe a partially written recording.
+    pub(super) fn write(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Returns `true` if the recording was taken for the given location.
+    pub(super) fn is_for(&self, latitude: f64, longitude: f64, altitude: Option<i32>) -> bool {
+        self.latitude == latitude && self.longitude == longitude && self.altitude == altitude
+    }
+
+    /// Converts the recording back into the raw response it was created from.
+    pub(super) fn to_raw_response(&self) -> RawResponse {
+        RawResponse {
+            body: self.body.get().to_owned(),
+            expires: self.expires.clone(),
+            last_modified: self.last_modified.clone(),
+        }
+    }
+}
+
+/// Saves raw weather responses to a directory.
+#[derive(Debug)]
+pub(super) struct Recorder {
+    dir: PathBuf,
+}
+
+impl Recorder {
+    /// Creates a new `Recorder` which writes to `dir`. The directory is created if it does not
+    /// exist.
+    pub(super) fn new(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+
+        Ok(Recorder {
+            dir: dir.to_owned(),
+        })
+    }
+
+    /// Saves `response` fetched for the given location. Recordings are named after the time they
+    /// were taken. The name is only meant for humans, [`Replay`] orders recordings by the
+    /// timestamp stored inside them.
+    pub(super) fn record(
+        &self,
+        response: &RawResponse,
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<i32>,
+    ) -> Result<()> {
+        let recording = Recording::new(response, latitude, longitude, altitude)?;
+
+        let path = self.dir.join(format!(
+            "{}.json",
+            recording.timestamp.strftime("%Y%m%dT%H%M%S%.fZ")
+        ));
+
+        debug!(path = %path.display(), "recording weather response");
+
+        recording.write(&path)
+    }
+}
+
+/// Replays weather responses saved by a [`Recorder`].
+///
+/// Without `at`, all recordings are served in chronological order, one per fetch, starting over
+/// after the last one. With `at`, the latest recording taken at or before that instant is served
+/// on every fetch.
+///
+/// The weather data is served exactly as recorded. To reproduce an image, render it for the time
+/// the recording was taken, e.g. via the `at` query parameter.
+#[derive(Debug)]
+pub struct Replay {
+    recordings: Vec<Recording>,
+    at: Option<Timestamp>,
+    next: AtomicUsize,
+}
+
+impl Replay {
+    /// Creates a new `Replay` from the recordings in `dir`.
+    pub fn new(dir: &Path, at: Option<Timestamp>) -> Result<Self> {
+        let mut recordings = Vec::new();
+
+        for entry in dir.read_dir()? {
+            let path = entry?.path();
+
+            if path.extension().is_some_and(|ext| ext == "json") {
+                recordings.push(Recording::read(&path)?);
+            }
+        }
+
+        if recordings.is_empty() {
+            return Err(Error::new(format!(
+                "no weather recordings found in {}",
+                dir.display()
+            )));
+        }
+
+        recordings.sort_by_key(|recording| recording.timestamp);
+
+        Ok(Replay {
+            recordings,
+            at,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    fn select(&self) -> Result<&Recording> {
+        match self.at {
+            Some(at) => self
+                .recordings
+                .iter()
+                .rev()
+                .find(|recording| recording.timestamp <= at)
+                .ok_or_else(|| Error::new(format!("no weather recording at or before {at}"))),
+            None => {
+                let index = self.next.fetch_add(1, Ordering::Relaxed) % self.recordings.len();
+                Ok(&self.recordings[index])
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for Replay {
+    async fn fetch(&self, hours: usize) -> Result<WeatherData> {
+        let recording = self.select()?;
+
+        debug!(timestamp = %recording.timestamp, "replaying weather response");
+
+        let body: Body = serde_json::from_str(recording.body.get())?;
+        WeatherData::from_body(&body, hours)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jiff::SignedDuration;
+
+    const BODY: &str = include_str!("testdata/metno_locationforecast.json");
+
+    fn response() -> RawResponse {
+        RawResponse {
+            body: BODY.to_owned(),
+            expires: Some("Sat, 14 Jun 2025 12:30:00 GMT".to_owned()),
+            last_modified: Some("Sat, 14 Jun 2025 12:00:00 GMT".to_owned()),
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::new(dir.path()).unwrap();
+        recorder
+            .record(&response(), 52.52, 13.405, Some(34))
+            .unwrap();
+
+        let replay = Replay::new(dir.path(), None).unwrap();
+        let recording = &replay.recordings[0];
+        assert_eq!(replay.recordings.len(), 1);
+        assert!(recording.is_for(52.52, 13.405, Some(34)));
+        assert!(!recording.is_for(52.52, 13.405, None));
+
+        let data = replay.fetch(24).await.unwrap();
+        let body: Body = serde_json::from_str(BODY).unwrap();
+        let expected = WeatherData::from_body(&body, 24).unwrap();
+
+        // The data is replayed unshifted.
+        assert_eq!(data.current.timestamp, expected.current.timestamp);
+        assert_eq!(
+            data.forecasts[23].timestamp,
+            expected.forecasts[23].timestamp
+        );
+        assert_eq!(data.current.air_temperature, 11.3);
+
+        let raw = recording.to_raw_response();
+        assert_eq!(raw.body, BODY.trim());
+        assert_eq!(raw.expires, response().expires);
+        assert_eq!(raw.last_modified, response().last_modified);
+    }
+
+    #[test]
+    fn select_at() {
+        let dir = tempfile::tempdir().unwrap();
+        let start: Timestamp = "2025-06-14T12:00:00Z".parse().unwrap();
+
+        // Written in reverse order, so that the file system order does not match.
+        for hours in [2, 1, 0] {
+            let mut recording = Recording::new(&response(), 52.52, 13.405, None).unwrap();
+            recording.timestamp = start
+                .checked_add(SignedDuration::from_hours(hours))
+                .unwrap();
+            recording
+                .write(&dir.path().join(format!("{hours}.json")))
+                .unwrap();
+        }
+
+        let select = |at: Timestamp| {
+            Replay::new(dir.path(), Some(at))
+                .unwrap()
+                .select()
+                .map(|recording| recording.timestamp)
+        };
+
+        let at = |mins| start.checked_add(SignedDuration::from_mins(mins)).unwrap();
+
+        assert!(select(at(-1)).is_err());
+        assert_eq!(select(at(0)).unwrap(), at(0));
+        assert_eq!(select(at(90)).unwrap(), at(60));
+        assert_eq!(select(at(120)).unwrap(), at(120));
+        assert_eq!(select(at(600)).unwrap(), at(120));
+
+        // Without `at`, recordings are served in chronological order.
+        let replay = Replay::new(dir.path(), None).unwrap();
+        let order: Vec<_> = (0..4).map(|_| replay.select().unwrap().timestamp).collect();
+        assert_eq!(order, [at(0), at(60), at(120), at(0)]);
+    }
+}
@@ -0,0 +1,422 @@
+//! Synthetic weather data for visual testing.
+use super::{
+    Alert, Condition, Coords, DataPoint, Intensity, Observation, Precipitation, Severity,
+    WeatherData,
+};
+use crate::{config::Location, error::Result, sun::Sun};
+use jiff::{RoundMode, SignedDuration, Timestamp, TimestampRound, Unit};
+use rand::Rng;
+use serde::Deserialize;
+use std::f64::consts::PI;
+use tracing::info;
+
+// The number of past hours generated as history.
+const HISTORY_HOURS: i64 = 24;
+
+// Locations close to the poles, where it's polar night for most of the winter half-year.
+const NORTH_POLE: Coords = Coords {
+    latitude: 89.0,
+    longitude: 0.0,
+    altitude: 0.0,
+};
+const SOUTH_POLE: Coords = Coords {
+    latitude: -89.0,
+    longitude: 0.0,
+    altitude: 2800.0,
+};
+
+/// A named weather situation which can be rendered instead of the actual weather.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scenario {
+    /// Clear skies and temperatures well above 30 degrees Celsius.
+    Heatwave,
+    /// Heavy snow, strong wind and freezing temperatures.
+    Blizzard,
+    /// A hot day interrupted by a line of heavy thunderstorms, cooler and showery afterwards.
+    ThunderstormFront,
+    /// Dense fog in the morning which clears up to a sunny day.
+    ClearingFog,
+    /// Storm-force wind with low pressure and passing showers.
+    Gale,
+    /// A cold, clear polar night turning snowy. The location is moved close to whichever pole is
+    /// in its winter half-year.
+    PolarNight,
+}
+
+impl Scenario {
+    /// Generates weather data for the scenario at `location` with `hours` of hourly forecasts
+    /// starting at `now`. The `rng` adds small variations, so that the weather looks natural.
+    pub fn generate<R: Rng>(
+        self,
+        location: &Location,
+        now: Timestamp,
+        hours: usize,
+        rng: &mut R,
+    ) -> Result<WeatherData> {
+        info!(scenario = ?self, "generating synthetic weather data");
+
+        let coords = match self {
+            Scenario::PolarNight => polar_night_coords(now),
+            _ => Coords {
+                latitude: location.latitude,
+                longitude: location.longitude,
+                altitude: location.altitude.unwrap_or_default() as f64,
+            },
+        };
+
+        let start = now.round(
+            TimestampRound::new()
+                .smallest(Unit::Hour)
+                .mode(RoundMode::Trunc),
+        )?;
+
+        let mut data_point = |i: i64| -> Result<DataPoint> {
+            let timestamp = start.checked_add(SignedDuration::from_hours(i))?;
+            // The progress of the scenario, the past looks like the start.
+            let t = (i as f64 / hours as f64).clamp(0.0, 1.0);
+            Ok(self.data_point(&coords, timestamp, t, rng))
+        };
+
+        let mut history: Vec<Observation> = (-HISTORY_HOURS..0)
+            .map(|i| data_point(i).map(|dp| Observation::from(&dp)))
+            .collect::<Result<_>>()?;
+
+        let current = data_point(0)?;
+        history.push(Observation::from(&current));
+
+        let forecasts = (1..=hours as i64)
+            .map(&mut data_point)
+            .collect::<Result<_>>()?;
+
+        let alerts = self.alert(start, hours)?.into_iter().collect();
+
+        Ok(WeatherData {
+            coords,
+            current,
+            forecasts,
+            expires: None,
+            fetched_at: now,
+            stale: false,
+            alerts,
+            history,
+        })
+    }
+
+    // Generates the weather at `timestamp`, where `t` is the progress of the scenario between 0.0
+    // and 1.0.
+    fn data_point<R: Rng>(
+        self,
+        coords: &Coords,
+        timestamp: Timestamp,
+        t: f64,
+        rng: &mut R,
+    ) -> DataPoint {
+        use Intensity::*;
+
+        let diurnal = diurnal(coords, timestamp);
+        // Whether a shower passes in scenarios with showery weather.
+        let showers = rng.random_bool(0.4);
+        let mut jitter = |range: f64| rng.random_range(-range..=range);
+
+        let mut dp = match self {
+            Scenario::Heatwave => {
+                let cloud_area_fraction = 5.0 + jitter(5.0);
+
+                DataPoint {
+                    air_pressure_at_sea_level: 1022.0 + jitter(1.0),
+                    air_temperature: 34.0 + 5.0 * diurnal + jitter(0.5),
+                    cloud_area_fraction,
+                    condition: if cloud_area_fraction < 5.0 {
+                        Condition::ClearSky
+                    } else {
+                        Condition::Fair
+                    },
+                    relative_humidity: Some(30.0 - 10.0 * diurnal),
+                    wind_from_direction: 180.0 + jitter(20.0),
+                    wind_speed: 2.0 + jitter(1.0),
+                    ..Default::default()
+                }
+            }
+            Scenario::Blizzard => DataPoint {
+                air_pressure_at_sea_level: 980.0 + jitter(2.0),
+                air_temperature: -10.0 + diurnal + jitter(0.5),
+                cloud_area_fraction: 100.0,
+                condition: Condition::Snow(precipitation(Heavy, false, false)),
+                fog_area_fraction: 30.0 + jitter(10.0),
+                precipitation_amount: 3.0 + jitter(1.0),
+                relative_humidity: Some(95.0),
+                wind_from_direction: 20.0 + jitter(10.0),
+                wind_speed: 20.0 + jitter(3.0),
+                ..Default::default()
+            },
+            Scenario::ThunderstormFront => {
+                if t < 0.3 {
+                    // Hot and increasingly humid ahead of the front.
+                    DataPoint {
+                        air_pressure_at_sea_level: 1010.0 - 20.0 * t + jitter(0.5),
+                        air_temperature: 29.0 + 3.0 * diurnal + jitter(0.5),
+                        cloud_area_fraction: 20.0 + 100.0 * t + jitter(5.0),
+                        condition: Condition::Fair,
+                        relative_humidity: Some(60.0 + 50.0 * t),
+                        wind_from_direction: 200.0 + jitter(20.0),
+                        wind_speed: 3.0 + jitter(1.0),
+                        ..Default::default()
+                    }
+                } else if t < 0.5 {
+                    // The front passes.
+                    DataPoint {
+                        air_pressure_at_sea_level: 1004.0 + jitter(1.0),
+                        air_temperature: 21.0 + jitter(1.0),
+                        cloud_area_fraction: 100.0,
+                        condition: Condition::Rain(precipitation(Heavy, true, true)),
+                        precipitation_amount: 10.0 + jitter(4.0),
+                        probability_of_thunder: 0.9,
+                        relative_humidity: Some(95.0),
+                        wind_from_direction: 270.0 + jitter(30.0),
+                        wind_speed: 12.0 + jitter(4.0),
+                        ..Default::default()
+                    }
+                } else {
+                    // Cooler and showery behind the front.
+                    DataPoint {
+                        air_pressure_at_sea_level: 1004.0 + 16.0 * (t - 0.5) + jitter(0.5),
+                        air_temperature: 18.0 + 2.0 * diurnal + jitter(0.5),
+                        cloud_area_fraction: 70.0 + jitter(20.0),
+                        condition: if showers {
+                            Condition::Rain(precipitation(Light, true, false))
+                        } else {
+                            Condition::PartlyCloudy
+                        },
+                        precipitation_amount: if showers { 0.5 } else { 0.0 },
+                        relative_humidity: Some(75.0),
+                        wind_from_direction: 290.0 + jitter(20.0),
+                        wind_speed: 6.0 + jitter(2.0),
+                        ..Default::default()
+                    }
+                }
+            }
+            Scenario::ClearingFog => {
+                let fog = (1.0 - t / 0.3).max(0.0);
+
+                DataPoint {
+                    air_pressure_at_sea_level: 1030.0 + jitter(0.5),
+                    air_temperature: 9.0 + 6.0 * diurnal + jitter(0.5),
+                    cloud_area_fraction: 10.0 + 80.0 * fog,
+                    condition: if fog > 0.0 {
+                        Condition::Fog
+                    } else if t < 0.5 {
+                        Condition::PartlyCloudy
+                    } else {
+                        Condition::ClearSky
+                    },
+                    fog_area_fraction: 100.0 * fog,
+                    relative_humidity: Some(70.0 + 30.0 * fog),
+                    wind_from_direction: 90.0 + jitter(45.0),
+                    wind_speed: 1.0 + 3.0 * t,
+                    ..Default::default()
+                }
+            }
+            Scenario::Gale => DataPoint {
+                air_pressure_at_sea_level: 972.0 + jitter(3.0),
+                air_temperature: 7.0 + diurnal + jitter(1.0),
+                cloud_area_fraction: 90.0 + jitter(10.0),
+                condition: if showers {
+                    Condition::Rain(precipitation(Normal, true, false))
+                } else {
+                    Condition::Cloudy
+                },
+                precipitation_amount: if showers { 1.0 + jitter(0.5) } else { 0.0 },
+                relative_humidity: Some(85.0),
+                wind_from_direction: 250.0 + jitter(15.0),
+                wind_speed: 23.0 + jitter(3.0),
+                ..Default::default()
+            },
+            Scenario::PolarNight => {
+                let snow = t > 0.6;
+
+                DataPoint {
+                    air_pressure_at_sea_level: 1030.0 - 20.0 * t + jitter(1.0),
+                    air_temperature: -25.0 + 10.0 * t + jitter(1.0),
+                    cloud_area_fraction: if snow { 90.0 } else { 5.0 + jitter(5.0) },
+                    condition: if snow {
+                        Condition::Snow(precipitation(Light, false, false))
+                    } else {
+                        Condition::ClearSky
+                    },
+                    precipitation_amount: if snow { 0.3 } else { 0.0 },
+                    relative_humidity: Some(80.0),
+                    wind_from_direction: 0.0,
+                    wind_speed: 3.0 + jitter(1.0),
+                    ..Default::default()
+                }
+            }
+        };
+
+        // Derive the remaining details, so that they agree with the values above.
+        dp.timestamp = timestamp;
+        dp.cloud_area_fraction = dp.cloud_area_fraction.clamp(0.0, 100.0);
+        dp.fog_area_fraction = dp.fog_area_fraction.clamp(0.0, 100.0);
+        dp.precipitation_amount = dp.precipitation_amount.max(0.0);
+        dp.relative_humidity = dp.relative_humidity.map(|rh| rh.clamp(5.0, 100.0));
+        dp.dew_point_temperature = dp
+            .relative_humidity
+            .map(|rh| dew_point(dp.air_temperature, rh));
+        dp.wind_from_direction = dp.wind_from_direction.rem_euclid(360.0);
+        dp.wind_speed = dp.wind_speed.max(0.0);
+        dp.wind_speed_of_gust = Some(dp.wind_speed * 1.5);
+
+        dp
+    }
+
+    // Returns the official warning which goes along with the scenario, if any.
+    fn alert(self, start: Timestamp, hours: usize) -> Result<Option<Alert>> {
+        let severity = match self {
+            Scenario::Blizzard => Severity::Extreme,
+            Scenario::Gale => Severity::Severe,
+            Scenario::ThunderstormFront => Severity::Moderate,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Alert {
+            severity,
+            onset: start,
+            expires: start.checked_add(SignedDuration::from_hours(hours as i64))?,
+        }))
+    }
+}
+
+fn precipitation(intensity: Intensity, showers: bool, thunder: bool) -> Precipitation {
+    Precipitation {
+        intensity,
+        showers,
+        thunder,
+    }
+}
+
+// Returns the daily variation of the temperature between -1.0 and 1.0, peaking in the afternoon
+// local solar time.
+fn diurnal(coords: &Coords, timestamp: Timestamp) -> f64 {
+    let utc_hours = timestamp.as_second().rem_euclid(86400) as f64 / 3600.0;
+    let solar_hours = utc_hours + coords.longitude / 15.0;
+    (2.0 * PI * (solar_hours - 15.0) / 24.0).cos()
+}
+
+// Approximates the dew point from temperature and relative humidity with the Magnus formula.
+fn dew_point(temperature: f64, relative_humidity: f64) -> f64 {
+    const B: f64 = 17.62;
+    const C: f64 = 243.12;
+
+    let gamma = (relative_humidity / 100.0).ln() + B * temperature / (C + temperature);
+    C * gamma / (B - gamma)
+}
+
+// Picks the pole which has the lower sun at `now`, i.e. the one in its winter half-year.
+fn polar_night_coords(now: Timestamp) -> Coords {
+    let altitude =
+        |coords: &Coords| Sun::new(coords.latitude, coords.longitude, None).altitude(now);
+
+    if altitude(&NORTH_POLE) < altitude(&SOUTH_POLE) {
+        NORTH_POLE
+    } else {
+        SOUTH_POLE
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sun::Daylight;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    const SCENARIOS: &[Scenario] = &[
+        Scenario::Heatwave,
+        Scenario::Blizzard,
+        Scenario::ThunderstormFront,
+        Scenario::ClearingFog,
+        Scenario::Gale,
+        Scenario::PolarNight,
+    ];
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn location() -> Location {
+        Location {
+            latitude: 52.52,
+            longitude: 13.405,
+            altitude: Some(38),
+            timezone: None,
+        }
+    }
+
+    #[test]
+    fn consistent() {
+        let now = ts("2025-06-14T12:34:56Z");
+
+        for &scenario in SCENARIOS {
+            let mut rng = StdRng::seed_from_u64(42);
+            let data = scenario.generate(&location(), now, 24, &mut rng).unwrap();
+
+            assert_eq!(data.current.timestamp, ts("2025-06-14T12:00:00Z"));
+            assert_eq!(data.forecasts.len(), 24);
+            assert_eq!(data.history.len(), 25);
+
+            for (i, dp) in data.forecasts.iter().enumerate() {
+                let hours = dp
+                    .timestamp
+                    .duration_since(data.current.timestamp)
+                    .as_hours();
+                assert_eq!(hours, i as i64 + 1);
+            }
+
+            for dp in std::iter::once(&data.current).chain(&data.forecasts) {
+                let precipitation = dp.condition.precipitation().is_some();
+                assert_eq!(precipitation, dp.precipitation_amount > 0.0, "{scenario:?}");
+                assert_eq!(dp.condition.thunder(), dp.probability_of_thunder > 0.0);
+                assert!((0.0..=100.0).contains(&dp.cloud_area_fraction));
+                assert!(dp.dew_point_temperature.unwrap() <= dp.air_temperature + 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let now = ts("2025-06-14T12:00:00Z");
+        let generate = || {
+            let mut rng = StdRng::seed_from_u64(7);
+            Scenario::Gale
+                .generate(&location(), now, 12, &mut rng)
+                .unwrap()
+        };
+
+        let (a, b) = (generate(), generate());
+
+        for (a, b) in a.forecasts.iter().zip(&b.forecasts) {
+            assert_eq!(a.air_temperature, b.air_temperature);
+            assert_eq!(a.condition, b.condition);
+        }
+    }
+
+    #[test]
+    fn polar_night() {
+        for now in ["2025-01-10T12:00:00Z", "2025-06-21T12:00:00Z"] {
+            let now = ts(now);
+            let mut rng = StdRng::seed_from_u64(0);
+            let data = Scenario::PolarNight
+                .generate(&location(), now, 24, &mut rng)
+                .unwrap();
+
+            let coords = &data.coords;
+            let sun = Sun::new(coords.latitude, coords.longitude, None);
+            assert_eq!(sun.daylight(now), Daylight::PolarNight);
+        }
+    }
+
+    #[test]
+    fn names() {
+        let scenario: Scenario = serde_json::from_str("\"thunderstorm_front\"").unwrap();
+        assert_eq!(scenario, Scenario::ThunderstormFront);
+    }
+}